@@ -1,7 +1,14 @@
 const remove_candidates = require("./remove_candidates.json");
 
 module.exports = () => ({
-  remove_candidates: remove_candidates,
-  allow_user: {},
+  accounts: [{ account: "1", screen_name: "fake_account" }],
+  remove_candidates: {
+    users: remove_candidates,
+    total: remove_candidates.length,
+    next_cursor: null,
+  },
+  allowlist: [],
+  defer_user: {},
   confirm_user: {},
+  remove_users: {},
 });
@@ -1,21 +1,25 @@
-import { Grid } from "@material-ui/core";
+import {
+  Button,
+  FormControlLabel,
+  Grid,
+  MenuItem,
+  Select,
+  Switch,
+  TextField,
+  Typography,
+} from "@material-ui/core";
 import React, { useState } from "react";
-import { TwitterUser, useRemoveCandidates } from "./api";
+import {
+  CandidateQuery,
+  CandidateSort,
+  postRemoveUsers,
+  TwitterUser,
+  useAccounts,
+  useRemoveCandidates,
+  useRemoveJob,
+} from "./api";
 import { UserCard } from "./UserCard";
 
-const compareUsers = (a: TwitterUser, b: TwitterUser) => {
-  if (a.friends_count === 0) {
-    return -1;
-  }
-  if (b.friends_count === 0) {
-    return 1;
-  }
-
-  const aLast = a.status ? Date.parse(a.status.created_at) : 0;
-  const bLast = b.status ? Date.parse(b.status.created_at) : 0;
-  return aLast - bLast;
-};
-
 const App = () => {
   const [confirmed, setConfirmed] = useState<number[]>([]);
   const removeUser = (userId: number) => {
@@ -24,32 +28,151 @@ const App = () => {
     setConfirmed(next);
   };
 
-  const users = useRemoveCandidates() ?? [];
+  const accounts = useAccounts() ?? [];
+  const [selectedAccount, setSelectedAccount] = useState<string | undefined>();
+  const account = selectedAccount ?? accounts[0]?.account;
+
+  const [query, setQuery] = useState<CandidateQuery>({
+    sort: "last_tweet",
+    order: "asc",
+  });
+  const { users, total, hasMore, loadMore } = useRemoveCandidates(
+    account,
+    query
+  );
+  const [jobId, setJobId] = useState<number | null>(null);
+  const job = useRemoveJob(account, jobId);
+  const shownUsers = users.filter((user) => !confirmed.includes(user.id));
+  const removeShownUsers = async () => {
+    const userIds = shownUsers.map((user) => user.id);
+    setConfirmed([...confirmed, ...userIds]);
+    for (let i = 0; i < userIds.length; i += 100) {
+      const job = await postRemoveUsers(
+        account ?? "",
+        userIds.slice(i, i + 100)
+      );
+      setJobId(job.id);
+    }
+  };
+
   const rows = [[]] as TwitterUser[][];
-  users
-    .sort(compareUsers)
-    .filter((user) => !confirmed.includes(user.id))
-    .forEach((user) => {
-      if (rows[rows.length - 1].length === 6) {
-        rows.push([user]);
-      } else {
-        rows[rows.length - 1].push(user);
-      }
-    });
+  shownUsers.forEach((user) => {
+    if (rows[rows.length - 1].length === 6) {
+      rows.push([user]);
+    } else {
+      rows[rows.length - 1].push(user);
+    }
+  });
 
   return (
     <div>
+      <Select
+        value={account ?? ""}
+        onChange={(e) => setSelectedAccount(e.target.value as string)}
+      >
+        {accounts.map(({ account, screen_name }) => (
+          <MenuItem key={account} value={account}>
+            @{screen_name}
+          </MenuItem>
+        ))}
+      </Select>
+      <Select
+        value={query.sort}
+        onChange={(e) =>
+          setQuery({ ...query, sort: e.target.value as CandidateSort })
+        }
+      >
+        <MenuItem value="last_tweet">Last tweet</MenuItem>
+        <MenuItem value="followers_count">Followers</MenuItem>
+        <MenuItem value="follow_duration">Follow duration</MenuItem>
+      </Select>
+      <Select
+        value={query.order}
+        onChange={(e) =>
+          setQuery({ ...query, order: e.target.value as "asc" | "desc" })
+        }
+      >
+        <MenuItem value="asc">Ascending</MenuItem>
+        <MenuItem value="desc">Descending</MenuItem>
+      </Select>
+      <FormControlLabel
+        control={
+          <Switch
+            checked={query.protected === false}
+            onChange={(e) =>
+              setQuery({
+                ...query,
+                protected: e.target.checked ? false : undefined,
+              })
+            }
+          />
+        }
+        label="Hide protected"
+      />
+      <FormControlLabel
+        control={
+          <Switch
+            checked={query.verified === false}
+            onChange={(e) =>
+              setQuery({
+                ...query,
+                verified: e.target.checked ? false : undefined,
+              })
+            }
+          />
+        }
+        label="Hide verified"
+      />
+      <TextField
+        label="Inactive days"
+        type="number"
+        value={query.inactive_days ?? ""}
+        onChange={(e) => {
+          const days = parseInt(e.target.value, 10);
+          setQuery({
+            ...query,
+            inactive_days: isNaN(days) ? undefined : days,
+          });
+        }}
+      />
+      {total !== undefined && (
+        <Typography variant="body2" component="p">
+          {total} candidates
+        </Typography>
+      )}
+      <Button
+        variant="contained"
+        color="secondary"
+        disabled={shownUsers.length === 0}
+        onClick={removeShownUsers}
+      >
+        Remove all shown
+      </Button>
+      {job && (
+        <Typography variant="body2" component="p">
+          {`Remove job ${job.id}: `}
+          {Object.entries(job.progress)
+            .map(([status, count]) => `${count} ${status}`)
+            .join(", ")}
+        </Typography>
+      )}
       {rows.map((row, i) => (
         <Grid key={i} container spacing={3}>
           {row.map((user) => (
             <UserCard
               key={user.screen_name}
+              account={account ?? ""}
               user={user}
               removeUser={removeUser}
             />
           ))}
         </Grid>
       ))}
+      {hasMore && (
+        <Button variant="contained" onClick={loadMore}>
+          Load more
+        </Button>
+      )}
     </div>
   );
 };
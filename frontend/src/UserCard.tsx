@@ -11,7 +11,12 @@ import {
 } from "@material-ui/core";
 import { red } from "@material-ui/core/colors";
 import React from "react";
-import { postConfirmRemove, TwitterUser } from "./api";
+import {
+  postAllowUser,
+  postConfirmRemove,
+  postDeferUser,
+  TwitterUser,
+} from "./api";
 
 const useStyles = makeStyles((theme) => ({
   root: {
@@ -36,6 +41,7 @@ const useStyles = makeStyles((theme) => ({
   },
 }));
 interface Props {
+  account: string;
   user: TwitterUser;
   removeUser: (userId: number) => void;
 }
@@ -103,16 +109,26 @@ export const UserCard = (props: Props) => {
             color="primary"
             onClick={async () => {
               props.removeUser(user.id);
+              await postAllowUser(props.account, user.id);
             }}
           >
             Allow
           </Button>
+          <Button
+            variant="contained"
+            onClick={async () => {
+              props.removeUser(user.id);
+              await postDeferUser(props.account, user.id, 30);
+            }}
+          >
+            Later
+          </Button>
           <Button
             variant="contained"
             color="secondary"
             onClick={async () => {
               props.removeUser(user.id);
-              await postConfirmRemove(user.id);
+              await postConfirmRemove(props.account, user.id);
             }}
           >
             Remove
@@ -1,4 +1,4 @@
-import useSWR from "swr";
+import useSWR, { useSWRInfinite } from "swr";
 
 export interface TwitterUser {
   id: number;
@@ -17,19 +17,84 @@ export interface TwitterStatus {
   created_at: string;
 }
 
-export const useRemoveCandidates = () => {
+export interface Account {
+  account: string;
+  screen_name: string;
+}
+
+export const useAccounts = () => {
   const fetcher = (url: string) =>
     fetch(url)
       .then((response) => response.json())
-      .then((response) => response as TwitterUser[]);
-  return useSWR<TwitterUser[]>("/remove_candidates", fetcher, {
+      .then((response) => response as Account[]);
+  return useSWR<Account[]>("/accounts", fetcher, {
     revalidateOnFocus: false,
     revalidateOnReconnect: false,
   }).data;
 };
 
-export const postConfirmRemove = async (user_id: number) => {
-  const response = await fetch("/remove_user", {
+export type CandidateSort = "last_tweet" | "followers_count" | "follow_duration";
+
+export interface CandidateQuery {
+  sort: CandidateSort;
+  order: "asc" | "desc";
+  protected?: boolean;
+  verified?: boolean;
+  inactive_days?: number;
+}
+
+export interface RemoveCandidatesResponse {
+  users: TwitterUser[];
+  total: number;
+  next_cursor: string | null;
+}
+
+export const useRemoveCandidates = (
+  account: string | undefined,
+  query: CandidateQuery
+) => {
+  const fetcher = (url: string) =>
+    fetch(url)
+      .then((response) => response.json())
+      .then((response) => response as RemoveCandidatesResponse);
+  const getKey = (
+    pageIndex: number,
+    previousPage: RemoveCandidatesResponse | null
+  ) => {
+    if (!account || (previousPage && !previousPage.next_cursor)) {
+      return null;
+    }
+    const params = new URLSearchParams();
+    Object.entries(query).forEach(([key, value]) => {
+      if (value !== undefined) {
+        params.set(key, String(value));
+      }
+    });
+    if (previousPage?.next_cursor) {
+      params.set("cursor", previousPage.next_cursor);
+    }
+    return `/accounts/${account}/remove_candidates?${params.toString()}`;
+  };
+  const { data, size, setSize } = useSWRInfinite<RemoveCandidatesResponse>(
+    getKey,
+    fetcher,
+    {
+      revalidateOnFocus: false,
+      revalidateOnReconnect: false,
+    }
+  );
+  const pages = data ?? [];
+  const lastPage = pages[pages.length - 1];
+  return {
+    users: pages.flatMap((page) => page.users),
+    total: pages[0]?.total,
+    hasMore: pages.length === size && !!lastPage?.next_cursor,
+    loadMore: () => setSize(size + 1),
+  };
+};
+
+export const postConfirmRemove = async (account: string, user_id: number) => {
+  const response = await fetch(`/accounts/${account}/remove_user`, {
     method: "POST",
     headers: {
       "Content-Type": "application/json",
@@ -38,3 +103,67 @@ export const postConfirmRemove = async (user_id: number) => {
   });
   return await response.json();
 };
+
+export const postAllowUser = async (account: string, user_id: number) => {
+  const response = await fetch(`/accounts/${account}/allowlist`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ user_id }),
+  });
+  return await response.json();
+};
+
+export const postDeferUser = async (
+  account: string,
+  user_id: number,
+  days: number
+) => {
+  const response = await fetch(`/accounts/${account}/defer_user`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ user_id, days }),
+  });
+  return await response.json();
+};
+
+export interface RemoveJob {
+  id: number;
+  created_at: number;
+  progress: { [status: string]: number };
+  users: {
+    user_id: number;
+    status: string;
+    error: string | null;
+    attempts: number;
+  }[];
+}
+
+export const postRemoveUsers = async (account: string, user_ids: number[]) => {
+  const response = await fetch(`/accounts/${account}/remove_users`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ user_ids }),
+  });
+  return (await response.json()) as RemoveJob;
+};
+
+export const useRemoveJob = (account: string | undefined, id: number | null) => {
+  const fetcher = (url: string) =>
+    fetch(url)
+      .then((response) => response.json())
+      .then((response) => response as RemoveJob);
+  return useSWR<RemoveJob>(
+    account && id !== null ? `/accounts/${account}/remove_jobs/${id}` : null,
+    fetcher,
+    {
+      refreshInterval: 60 * 1000,
+      revalidateOnFocus: false,
+    }
+  ).data;
+};
@@ -0,0 +1,5 @@
+ALTER TABLE friends_ids
+    ADD COLUMN IF NOT EXISTS created_at BIGINT NOT NULL DEFAULT 0;
+
+ALTER TABLE followers_ids
+    ADD COLUMN IF NOT EXISTS created_at BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,19 @@
+-- Rows synced before accounts existed are left with an empty account until `main` moves them to
+-- the first account.
+ALTER TABLE friends_ids
+    ADD COLUMN account TEXT NOT NULL DEFAULT '';
+ALTER TABLE friends_ids
+    DROP CONSTRAINT friends_ids_pkey;
+ALTER TABLE friends_ids
+    ADD PRIMARY KEY (account, id);
+ALTER TABLE friends_ids
+    ALTER COLUMN account DROP DEFAULT;
+
+ALTER TABLE followers_ids
+    ADD COLUMN account TEXT NOT NULL DEFAULT '';
+ALTER TABLE followers_ids
+    DROP CONSTRAINT followers_ids_pkey;
+ALTER TABLE followers_ids
+    ADD PRIMARY KEY (account, id);
+ALTER TABLE followers_ids
+    ALTER COLUMN account DROP DEFAULT;
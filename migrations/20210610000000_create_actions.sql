@@ -0,0 +1,15 @@
+CREATE TABLE actions
+(
+    id         BIGSERIAL NOT NULL,
+    account    TEXT      NOT NULL,
+    actor      TEXT      NOT NULL,
+    target_id  BIGINT    NOT NULL,
+    action     TEXT      NOT NULL,
+    reason     TEXT      NOT NULL,
+    result     TEXT      NOT NULL,
+    error      TEXT,
+    created_at BIGINT    NOT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX actions_account_id_idx ON actions (account, id);
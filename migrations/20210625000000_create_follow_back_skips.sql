@@ -0,0 +1,8 @@
+CREATE TABLE follow_back_skips
+(
+    account    TEXT   NOT NULL,
+    user_id    BIGINT NOT NULL,
+    reason     TEXT   NOT NULL,
+    skipped_at BIGINT NOT NULL,
+    PRIMARY KEY (account, user_id)
+);
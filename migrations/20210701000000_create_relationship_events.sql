@@ -0,0 +1,12 @@
+CREATE TABLE relationship_events
+(
+    id         BIGSERIAL NOT NULL,
+    account    TEXT      NOT NULL,
+    user_id    BIGINT    NOT NULL,
+    event      TEXT      NOT NULL,
+    created_at BIGINT    NOT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX relationship_events_account_id_idx ON relationship_events (account, id);
+CREATE INDEX relationship_events_account_user_id_idx ON relationship_events (account, user_id, id);
@@ -0,0 +1,18 @@
+CREATE TABLE snapshots
+(
+    id          BIGSERIAL NOT NULL,
+    account     TEXT      NOT NULL,
+    follower    BOOLEAN   NOT NULL,
+    started_at  BIGINT    NOT NULL,
+    finished_at BIGINT,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX snapshots_account_follower_id_idx ON snapshots (account, follower, id);
+
+CREATE TABLE snapshot_members
+(
+    snapshot_id BIGINT NOT NULL REFERENCES snapshots (id) ON DELETE CASCADE,
+    user_id     BIGINT NOT NULL,
+    PRIMARY KEY (snapshot_id, user_id)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE webhook_cursors
+(
+    account       TEXT   NOT NULL,
+    url           TEXT   NOT NULL,
+    last_event_id BIGINT NOT NULL,
+    PRIMARY KEY (account, url)
+);
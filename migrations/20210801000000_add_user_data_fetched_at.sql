@@ -0,0 +1,4 @@
+-- Profiles stored before this migration are treated as the oldest ones.
+ALTER TABLE user_data ADD COLUMN IF NOT EXISTS fetched_at BIGINT NOT NULL DEFAULT 0;
+
+CREATE INDEX user_data_fetched_at_idx ON user_data (fetched_at);
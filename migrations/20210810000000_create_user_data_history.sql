@@ -0,0 +1,14 @@
+CREATE TABLE user_data_history
+(
+    id         BIGSERIAL NOT NULL,
+    user_id    BIGINT    NOT NULL,
+    data       JSONB     NOT NULL,
+    fetched_at BIGINT    NOT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX user_data_history_user_id_id_idx ON user_data_history (user_id, id);
+
+INSERT INTO user_data_history (user_id, data, fetched_at)
+SELECT id, data - 'status' - 'statuses_count' - 'favourites_count', fetched_at
+FROM user_data WHERE data IS NOT NULL ORDER BY id;
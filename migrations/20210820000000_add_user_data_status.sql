@@ -0,0 +1 @@
+ALTER TABLE user_data ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'active';
@@ -0,0 +1,12 @@
+CREATE TABLE action_queue
+(
+    id         BIGSERIAL NOT NULL,
+    account    TEXT      NOT NULL,
+    action     TEXT      NOT NULL,
+    user_id    BIGINT    NOT NULL,
+    actor      TEXT      NOT NULL,
+    reason     TEXT      NOT NULL,
+    created_at BIGINT    NOT NULL,
+    PRIMARY KEY (id),
+    UNIQUE (account, action, user_id)
+);
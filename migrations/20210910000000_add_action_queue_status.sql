@@ -0,0 +1,13 @@
+ALTER TABLE action_queue
+    ADD COLUMN status          TEXT    NOT NULL DEFAULT 'pending',
+    ADD COLUMN attempts        INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN last_error      TEXT,
+    ADD COLUMN next_attempt_at BIGINT  NOT NULL DEFAULT 0,
+    ADD COLUMN updated_at      BIGINT  NOT NULL DEFAULT 0;
+UPDATE action_queue SET updated_at = created_at;
+
+-- Finished entries are kept, so only one open entry per action and user is allowed.
+ALTER TABLE action_queue DROP CONSTRAINT action_queue_account_action_user_id_key;
+CREATE UNIQUE INDEX action_queue_open_idx ON action_queue (account, action, user_id)
+    WHERE status IN ('pending', 'running');
+CREATE INDEX action_queue_account_status_idx ON action_queue (account, status, id);
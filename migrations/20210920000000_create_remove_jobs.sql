@@ -0,0 +1,16 @@
+CREATE TABLE remove_jobs
+(
+    id         BIGSERIAL NOT NULL,
+    account    TEXT      NOT NULL,
+    created_at BIGINT    NOT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE TABLE remove_job_users
+(
+    job_id         BIGINT NOT NULL REFERENCES remove_jobs (id) ON DELETE CASCADE,
+    user_id        BIGINT NOT NULL,
+    action_id      BIGINT,
+    skipped_reason TEXT,
+    PRIMARY KEY (job_id, user_id)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE unfollow_allowlist
+(
+    account    TEXT   NOT NULL,
+    user_id    BIGINT NOT NULL,
+    reason     TEXT   NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (account, user_id)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE deferred_users
+(
+    account    TEXT   NOT NULL,
+    user_id    BIGINT NOT NULL,
+    until      BIGINT NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (account, user_id)
+);
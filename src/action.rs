@@ -0,0 +1,172 @@
+use crate::current_time_duration;
+use crate::sql::{
+    ActionLogClient, ActionQueueClient, AllowlistClient, BlocklistClient, EnqueuedAction,
+    NewAction, NewQueuedAction,
+};
+use crate::twitter::TwitterApi;
+use anyhow::Result;
+use egg_mode::user::TwitterUser;
+use std::fmt::{Display, Formatter};
+
+const SUCCEEDED: &str = "succeeded";
+const FAILED: &str = "failed";
+const REFUSED: &str = "refused";
+const DRY_RUN: &str = "dry_run";
+
+/// Results of the actions that called the API, which is what the quotas limit. Refusals are
+/// recorded without one.
+pub(crate) const API_CALL_RESULTS: &[&str] = &[SUCCEEDED, FAILED];
+/// In dry-run mode, the dry runs count against the quotas in place of the calls they stand for.
+pub(crate) const DRY_RUN_QUOTA_RESULTS: &[&str] = &[SUCCEEDED, FAILED, DRY_RUN];
+
+#[derive(Clone, Copy, Debug)]
+pub enum ActionKind {
+    Follow,
+    Unfollow,
+}
+
+impl ActionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActionKind::Follow => "follow",
+            ActionKind::Unfollow => "unfollow",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "follow" => Some(ActionKind::Follow),
+            "unfollow" => Some(ActionKind::Unfollow),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Actor {
+    FollowBackWorker,
+    InvalidUserRemover,
+    RemoveUserHandler,
+}
+
+impl Actor {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Actor::FollowBackWorker => "follow_back_worker",
+            Actor::InvalidUserRemover => "invalid_user_remover",
+            Actor::RemoveUserHandler => "remove_user",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "follow_back_worker" => Some(Actor::FollowBackWorker),
+            "invalid_user_remover" => Some(Actor::InvalidUserRemover),
+            "remove_user" => Some(Actor::RemoveUserHandler),
+            _ => None,
+        }
+    }
+}
+
+/// Returned by `perform_action` when the action is not allowed, without calling the API.
+#[derive(Debug)]
+pub struct ActionRefused(pub String);
+impl Display for ActionRefused {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.0)
+    }
+}
+impl std::error::Error for ActionRefused {}
+
+/// Follows or unfollows `user_id` and records the attempt in the action log.
+///
+/// Follows of users on the account's blocklist, and unfollows of users on its allowlist other than
+/// manual ones, are refused without calling the API. In dry-run
+/// mode the API is not called either; the action is only recorded and `None` is returned.
+pub(crate) async fn perform_action<P, T>(
+    pool: &P,
+    client: &T,
+    kind: ActionKind,
+    user_id: u64,
+    actor: Actor,
+    reason: &str,
+    dry_run: bool,
+) -> Result<Option<TwitterUser>>
+where
+    P: ActionLogClient + BlocklistClient + AllowlistClient,
+    T: TwitterApi,
+{
+    let account = client.account();
+    let refusal = match (kind, actor) {
+        (ActionKind::Follow, _) if pool.is_blocked_user(account, user_id as i64).await? => {
+            Some("user is on the follow blocklist")
+        }
+        (ActionKind::Unfollow, Actor::FollowBackWorker | Actor::InvalidUserRemover)
+            if pool.is_allowed_user(account, user_id as i64).await? =>
+        {
+            Some("user is on the unfollow allowlist")
+        }
+        _ => None,
+    };
+
+    let response = match (refusal, kind) {
+        (Some(refusal), _) => Err(ActionRefused(format!(
+            "Refused to {} {}: {}",
+            kind.as_str(),
+            user_id,
+            refusal
+        ))
+        .into()),
+        (None, _) if dry_run => Ok(None),
+        (None, ActionKind::Follow) => client.follow(user_id).await.map(Some),
+        (None, ActionKind::Unfollow) => client.unfollow(user_id).await.map(Some),
+    };
+    let (result, error) = match (&response, refusal) {
+        (_, Some(refusal)) => (REFUSED, Some(refusal.to_string())),
+        (Ok(None), None) => (DRY_RUN, None),
+        (Ok(Some(_)), None) => (SUCCEEDED, None),
+        (Err(e), None) => (FAILED, Some(format!("{:?}", e))),
+    };
+    pool.put_action(&NewAction {
+        account,
+        actor: actor.as_str(),
+        target_id: user_id as i64,
+        action: kind.as_str(),
+        reason,
+        result,
+        error,
+        created_at: current_time_duration().as_secs() as i64,
+    })
+    .await?;
+    response
+}
+
+/// Queues the action for `ActionScheduler`, or returns the entry it is already queued as.
+///
+/// A manual request takes over an entry queued by a worker, so that the scheduler no longer
+/// cancels it as obsolete.
+pub(crate) async fn enqueue_action<P: ActionQueueClient>(
+    pool: &P,
+    account: &str,
+    kind: ActionKind,
+    user_id: u64,
+    actor: Actor,
+    reason: &str,
+) -> Result<EnqueuedAction> {
+    let now = current_time_duration().as_secs() as i64;
+    let enqueued = pool
+        .enqueue_action(&NewQueuedAction {
+            account,
+            action: kind.as_str(),
+            user_id: user_id as i64,
+            actor: actor.as_str(),
+            reason,
+            created_at: now,
+        })
+        .await?;
+    if !enqueued.created && matches!(actor, Actor::RemoveUserHandler) {
+        pool.reassign_queued_action(enqueued.id, actor.as_str(), reason, now)
+            .await?;
+    }
+    Ok(enqueued)
+}
@@ -10,9 +10,12 @@ async fn main() -> Result<()> {
     env_logger::init();
     let sql_url = std::env::var("SQL_URL")?;
     let pool = PgPool::connect(&sql_url).await?;
+    let account = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("Usage: get_old_followers <account user id>"))?;
 
-    let followers = pool.get_all_user_id_entries(true).await?;
-    let friends = pool.get_all_user_id_entries(false).await?;
+    let followers = pool.get_all_user_id_entries(&account, true).await?;
+    let friends = pool.get_all_user_id_entries(&account, false).await?;
 
     let mut follower_confirmed_map = BTreeMap::new();
     for follower in followers {
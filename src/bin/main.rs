@@ -3,68 +3,255 @@ use anyhow::Result;
 use rand::thread_rng;
 use sqlx::PgPool;
 use std::io::stdin;
-use twitter_pipeline::server::{get_remove_candidates, get_user_info, remove_user};
-use twitter_pipeline::twitter::TwitterClient;
+use twitter_pipeline::config::{load_config, Config};
+use twitter_pipeline::credentials::{
+    load_credentials, save_credentials, verify_credentials, Credentials,
+};
+use twitter_pipeline::server::{self, Accounts, DryRun};
+use twitter_pipeline::sql::{
+    get_migration_status, move_legacy_ids, run_migrations, ActionLogClient, ActionQueueClient,
+    AllowlistClient, BlocklistClient, DeferredUserClient, InMemoryPool, PgPoolExt,
+    RelationshipEventClient, RemoveJobClient, SkippedUserClient, SnapshotClient, UserHistoryClient,
+    UserStatusClient, WebhookCursorClient,
+};
+use twitter_pipeline::twitter::{RateLimiter, TwitterClient};
 use twitter_pipeline::worker::InvalidUserRemover;
 use twitter_pipeline::worker::UserIdSynchronizer;
-use twitter_pipeline::worker::{FollowBackWorker, UserDataSynchronizer};
+use twitter_pipeline::worker::{
+    ActionScheduler, FollowBackWorker, UserDataSynchronizer, WebhookNotifier,
+};
 
 #[actix_web::main]
 async fn main() -> Result<()> {
     dotenv::dotenv()?;
     env_logger::init();
+
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let dry_run_flag = args.iter().any(|arg| arg == "--dry-run");
+    let in_memory = args.iter().any(|arg| arg == "--in-memory");
+    args.retain(|arg| arg != "--dry-run" && arg != "--in-memory");
+    let dry_run = dry_run_flag || is_dry_run_env();
+
+    match args.first().map(|arg| arg.as_str()) {
+        None => run(dry_run, in_memory).await,
+        Some("login") => login().await,
+        Some("migrate") => print_migration_status().await,
+        Some(command) => Err(anyhow::anyhow!("Unknown command: {}", command)),
+    }
+}
+
+async fn print_migration_status() -> Result<()> {
     let sql_url = std::env::var("SQL_URL")?;
     let pool = PgPool::connect(&sql_url).await?;
+    for migration in get_migration_status(&pool).await? {
+        let status = if migration.applied {
+            "applied"
+        } else {
+            "pending"
+        };
+        println!(
+            "{} {} ({})",
+            migration.version, migration.description, status
+        );
+    }
+    Ok(())
+}
+
+fn is_dry_run_env() -> bool {
+    match std::env::var("DRY_RUN") {
+        Ok(value) => !matches!(value.as_str(), "" | "0" | "false"),
+        Err(_) => false,
+    }
+}
+
+fn config_path() -> String {
+    std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config.json".to_string())
+}
 
+fn credentials_path() -> String {
+    std::env::var("CREDENTIALS_PATH").unwrap_or_else(|_| "credentials.json".to_string())
+}
+
+fn consumer_token() -> Result<egg_mode::KeyPair> {
     let consumer_key = std::env::var("CONSUMER_KEY")?;
     let consumer_secret = std::env::var("CONSUMER_SECRET")?;
-    let token = egg_mode::KeyPair::new(consumer_key, consumer_secret);
+    Ok(egg_mode::KeyPair::new(consumer_key, consumer_secret))
+}
+
+async fn login() -> Result<()> {
+    let token = consumer_token()?;
     let request_token = egg_mode::auth::request_token(&token, "oob").await?;
     let url = egg_mode::auth::authorize_url(&request_token);
-    log::info!("URL: {}", url);
+    println!("Open the following URL and enter the PIN: {}", url);
 
     let input = read_input_line()?;
-    let (token, _, screen_name) =
+    let (token, user_id, screen_name) =
         egg_mode::auth::access_token(token, &request_token, input.trim()).await?;
-    let client = TwitterClient { token, screen_name };
-
-    let followers_ids_syncer = UserIdSynchronizer {
-        pool: pool.clone(),
-        client: client.clone(),
-        follower: true,
-    };
-    let friends_ids_syncer = UserIdSynchronizer {
-        pool: pool.clone(),
-        client: client.clone(),
-        follower: false,
-    };
-    let follow_back_worker = FollowBackWorker {
-        pool: pool.clone(),
-        client: client.clone(),
-    };
-    let invalid_user_remover = InvalidUserRemover {
-        pool: pool.clone(),
-        client: client.clone(),
+    let access = match token {
+        egg_mode::Token::Access { access, .. } => access,
+        egg_mode::Token::Bearer(_) => return Err(anyhow::anyhow!("Unexpected bearer token")),
     };
-    let user_data_syncer = UserDataSynchronizer {
-        pool: pool.clone(),
-        client: client.clone(),
-        rng: thread_rng(),
+    let credentials = Credentials {
+        user_id,
+        screen_name,
+        access_key: access.key.to_string(),
+        access_secret: access.secret.to_string(),
     };
 
-    followers_ids_syncer.run();
-    friends_ids_syncer.run();
-    follow_back_worker.start();
-    invalid_user_remover.start();
-    user_data_syncer.start();
+    let path = credentials_path();
+    let mut stored = load_credentials(&path)?;
+    let screen_name = credentials.screen_name.clone();
+    // Logging in again keeps the account's place; the first account owns the legacy rows.
+    match stored
+        .iter_mut()
+        .find(|stored| stored.user_id == credentials.user_id)
+    {
+        Some(stored) => *stored = credentials,
+        None => stored.push(credentials),
+    }
+    save_credentials(&path, &stored)?;
+    println!("Saved credentials of @{} to {}", screen_name, path);
+    Ok(())
+}
+
+async fn run(dry_run: bool, in_memory: bool) -> Result<()> {
+    let path = credentials_path();
+    let credentials = load_credentials(&path)?;
+    if credentials.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No credentials found at {}. Run `main login` first.",
+            path
+        ));
+    }
+
+    let config = load_config(config_path())?;
+    let consumer = consumer_token()?;
+    let mut clients = vec![];
+    for credentials in credentials.iter() {
+        let token = credentials.token(consumer.clone());
+        let user = verify_credentials(&token, &credentials.screen_name).await?;
+        if user.screen_name != credentials.screen_name {
+            log::info!("@{} is now @{}", credentials.screen_name, user.screen_name);
+        }
+        clients.push(TwitterClient {
+            token,
+            user_id: credentials.user_id,
+            account: credentials.user_id.to_string(),
+            screen_name: user.screen_name,
+            rate_limiter: RateLimiter::new(config.rate_limit),
+            retry: config.retry,
+        });
+    }
+
+    // The blocklist, the action log and the quotas are lost on restart, so keeping them in memory
+    // has to be asked for.
+    if in_memory {
+        log::warn!("--in-memory: Data will be lost when the process stops.");
+        return start(InMemoryPool::default(), clients, config, dry_run).await;
+    }
+    let sql_url = std::env::var("SQL_URL")
+        .map_err(|_| anyhow::anyhow!("SQL_URL is not set. Pass --in-memory to run without it."))?;
+    let pool = PgPool::connect(&sql_url).await?;
+    run_migrations(&pool).await?;
+    // The ids synced before accounts existed belong to the first account, which was the only one.
+    let owner = &clients[0].account;
+    let moved = move_legacy_ids(&pool, owner).await?;
+    if moved > 0 {
+        log::info!(
+            "Moved {} ids synced before accounts existed to {}",
+            moved,
+            owner
+        );
+    }
+    start(pool, clients, config, dry_run).await
+}
+
+async fn start<P>(pool: P, clients: Vec<TwitterClient>, config: Config, dry_run: bool) -> Result<()>
+where
+    P: PgPoolExt
+        + ActionLogClient
+        + BlocklistClient
+        + SkippedUserClient
+        + RelationshipEventClient
+        + SnapshotClient
+        + WebhookCursorClient
+        + UserHistoryClient
+        + UserStatusClient
+        + ActionQueueClient
+        + RemoveJobClient
+        + AllowlistClient
+        + DeferredUserClient
+        + Clone
+        + Send
+        + 'static,
+{
+    if dry_run {
+        log::warn!("Dry run: follows and unfollows are only recorded in the action log.");
+    }
+    for client in clients.iter() {
+        log::info!("Starting workers of @{}", client.screen_name);
+        let followers_ids_syncer = UserIdSynchronizer {
+            pool: pool.clone(),
+            client: client.clone(),
+            follower: true,
+        };
+        let friends_ids_syncer = UserIdSynchronizer {
+            pool: pool.clone(),
+            client: client.clone(),
+            follower: false,
+        };
+        let follow_back_worker = FollowBackWorker {
+            pool: pool.clone(),
+            client: client.clone(),
+            filters: config.follow_back_filters.clone(),
+        };
+        let invalid_user_remover = InvalidUserRemover {
+            pool: pool.clone(),
+            client: client.clone(),
+            rules: config.invalid_user_rules.clone(),
+        };
+        let action_scheduler = ActionScheduler {
+            pool: pool.clone(),
+            client: client.clone(),
+            config: config.action_scheduler,
+            dry_run,
+        };
+        let user_data_syncer = UserDataSynchronizer {
+            pool: pool.clone(),
+            client: client.clone(),
+            rng: thread_rng(),
+            refresh: config.user_data_refresh,
+        };
+
+        followers_ids_syncer.run();
+        friends_ids_syncer.run();
+        follow_back_worker.start();
+        invalid_user_remover.start();
+        action_scheduler.start();
+        user_data_syncer.start();
+
+        if !config.webhooks.is_empty() {
+            let webhook_notifier = WebhookNotifier {
+                pool: pool.clone(),
+                account: client.account.clone(),
+                screen_name: client.screen_name.clone(),
+                webhooks: config.webhooks.clone(),
+                retry: config.retry,
+            };
+            webhook_notifier.start();
+        }
+    }
 
+    let accounts = clients
+        .into_iter()
+        .map(|client| (client.account.clone(), client))
+        .collect::<Accounts<_>>();
     HttpServer::new(move || {
         App::new()
-            .service(get_remove_candidates)
-            .service(remove_user)
-            .service(get_user_info)
-            .data(client.clone())
+            .configure(server::config::<P, TwitterClient>)
+            .data(accounts.clone())
             .data(pool.clone())
+            .data(DryRun(dry_run))
     })
     .bind("0.0.0.0:8080")?
     .run()
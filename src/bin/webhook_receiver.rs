@@ -0,0 +1,42 @@
+use actix_web::web::{self, Bytes, Data};
+use actix_web::{App, HttpResponse, HttpServer};
+use anyhow::Result;
+use rand::prelude::*;
+
+/// Prints the notifications posted by `WebhookNotifier`, for testing webhooks locally.
+///
+/// Usage: webhook_receiver [port]. Setting `FAILURE_RATE` (0.0 to 1.0) makes it answer that share
+/// of the requests with 500 to exercise the retries.
+#[actix_web::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+    let port = std::env::args()
+        .nth(1)
+        .map(|port| port.parse::<u16>())
+        .transpose()?
+        .unwrap_or(8081);
+    let failure_rate = match std::env::var("FAILURE_RATE") {
+        Ok(rate) => rate.parse::<f64>()?,
+        Err(_) => 0.0,
+    };
+
+    log::info!("Listening on 0.0.0.0:{}", port);
+    HttpServer::new(move || {
+        App::new()
+            .data(failure_rate)
+            .route("/webhook", web::post().to(receive))
+    })
+    .bind(("0.0.0.0", port))?
+    .run()
+    .await?;
+    Ok(())
+}
+
+async fn receive(body: Bytes, failure_rate: Data<f64>) -> HttpResponse {
+    if thread_rng().gen_bool(failure_rate.clamp(0.0, 1.0)) {
+        log::warn!("Rejecting: {}", String::from_utf8_lossy(&body));
+        return HttpResponse::InternalServerError().finish();
+    }
+    println!("{}", String::from_utf8_lossy(&body));
+    HttpResponse::Ok().finish()
+}
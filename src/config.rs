@@ -0,0 +1,46 @@
+use crate::rules::{default_invalid_user_rules, Rule};
+use crate::twitter::{RateLimitConfig, RetryConfig};
+use crate::worker::{ActionSchedulerConfig, UserDataRefreshConfig, Webhook};
+use anyhow::Result;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub invalid_user_rules: Vec<Rule>,
+    /// Followers matching any of these rules are not followed back.
+    pub follow_back_filters: Vec<Rule>,
+    pub rate_limit: RateLimitConfig,
+    /// Also used for webhook deliveries.
+    pub retry: RetryConfig,
+    pub webhooks: Vec<Webhook>,
+    pub user_data_refresh: UserDataRefreshConfig,
+    pub action_scheduler: ActionSchedulerConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            invalid_user_rules: default_invalid_user_rules(),
+            follow_back_filters: vec![],
+            rate_limit: RateLimitConfig::default(),
+            retry: RetryConfig::default(),
+            webhooks: vec![],
+            user_data_refresh: UserDataRefreshConfig::default(),
+            action_scheduler: ActionSchedulerConfig::default(),
+        }
+    }
+}
+
+/// Loads the config file at `path`, falling back to the defaults when it does not exist.
+pub fn load_config<P: AsRef<Path>>(path: P) -> Result<Config> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let content = fs::read_to_string(path)?;
+    let config = serde_json::from_str(&content)?;
+    Ok(config)
+}
@@ -0,0 +1,148 @@
+use crate::twitter::TwitterError;
+use anyhow::{anyhow, Result};
+use egg_mode::user::TwitterUser;
+use egg_mode::{KeyPair, Token};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Serialize, Deserialize)]
+pub struct Credentials {
+    pub user_id: u64,
+    pub screen_name: String,
+    pub access_key: String,
+    pub access_secret: String,
+}
+
+impl Credentials {
+    pub fn token(&self, consumer: KeyPair) -> Token {
+        Token::Access {
+            consumer,
+            access: KeyPair::new(self.access_key.clone(), self.access_secret.clone()),
+        }
+    }
+}
+
+/// Keyfiles written before multiple accounts were supported hold a single object.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Keyfile {
+    Accounts(Vec<Credentials>),
+    Single(Credentials),
+}
+
+pub fn load_credentials<P: AsRef<Path>>(path: P) -> Result<Vec<Credentials>> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let content = fs::read_to_string(path)?;
+    let credentials = match serde_json::from_str(&content)? {
+        Keyfile::Accounts(credentials) => credentials,
+        Keyfile::Single(credentials) => vec![credentials],
+    };
+    Ok(credentials)
+}
+
+pub fn save_credentials<P: AsRef<Path>>(path: P, credentials: &[Credentials]) -> Result<()> {
+    let content = serde_json::to_string_pretty(credentials)?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // The mode above only applies when the file is created.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// Returns the current profile of the account, whose screen name may differ from the stored one.
+pub async fn verify_credentials(token: &Token, screen_name: &str) -> Result<TwitterUser> {
+    match egg_mode::auth::verify_tokens(token)
+        .await
+        .map_err(TwitterError::from)
+    {
+        Ok(response) => Ok(response.response),
+        Err(TwitterError::InvalidToken(_)) => Err(revoked_error(screen_name)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn revoked_error(screen_name: &str) -> anyhow::Error {
+    anyhow!(
+        "The stored access token of @{} has been revoked. Run `main login` to authorize again.",
+        screen_name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn keyfile_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn credentials(user_id: u64, screen_name: &str) -> Credentials {
+        Credentials {
+            user_id,
+            screen_name: screen_name.to_string(),
+            access_key: "key".to_string(),
+            access_secret: "secret".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_load_credentials() {
+        let path = keyfile_path("test_load_credentials");
+        assert!(load_credentials(&path).unwrap().is_empty());
+
+        save_credentials(&path, &[credentials(1, "me"), credentials(2, "alt")]).unwrap();
+        let loaded = load_credentials(&path).unwrap();
+        let accounts = loaded
+            .iter()
+            .map(|credentials| (credentials.user_id, credentials.screen_name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(accounts, vec![(1, "me"), (2, "alt")]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_load_credentials_legacy_keyfile() {
+        let path = keyfile_path("test_load_credentials_legacy_keyfile");
+        let legacy = serde_json::to_string(&credentials(1, "me")).unwrap();
+        fs::write(&path, legacy).unwrap();
+
+        let loaded = load_credentials(&path).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].user_id, 1);
+        assert_eq!(loaded[0].access_key, "key");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_credentials_restricts_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = keyfile_path("test_save_credentials_restricts_permissions");
+        fs::write(&path, "[]").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        save_credentials(&path, &[credentials(1, "me")]).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        fs::remove_file(&path).unwrap();
+    }
+}
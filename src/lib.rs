@@ -1,40 +1,80 @@
-use crate::sql::PgPoolExt;
-use std::collections::BTreeSet;
-use std::iter::FromIterator;
+use crate::sql::SnapshotClient;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+pub mod action;
+pub mod config;
+pub mod credentials;
+pub mod rules;
 pub mod server;
 pub mod sql;
 pub mod twitter;
 pub mod worker;
 
+#[cfg(test)]
+mod test_utils;
+
 pub fn current_time_duration() -> Duration {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Failed to get current UNIX time.")
 }
 
-pub(crate) async fn get_difference<P: PgPoolExt>(
+/// Compares the latest complete snapshots of followers and friends. Returns nothing until both
+/// have finished at least once.
+pub(crate) async fn get_difference<P: SnapshotClient>(
     pool: &P,
-    confirmed_after: i64,
+    account: &str,
     get_unfollowed_users: bool,
 ) -> anyhow::Result<Vec<i64>> {
-    let followers = pool.get_user_ids(true, confirmed_after).await?;
-    let friends = pool.get_user_ids(false, confirmed_after).await?;
+    let followers_snapshot = pool.get_complete_snapshot(account, true, None).await?;
+    let friends_snapshot = pool.get_complete_snapshot(account, false, None).await?;
+    let (followers_snapshot, friends_snapshot) = match (followers_snapshot, friends_snapshot) {
+        (Some(followers), Some(friends)) => (followers, friends),
+        _ => {
+            log::info!("Account {} has no complete snapshots yet", account);
+            return Ok(vec![]);
+        }
+    };
+    if get_unfollowed_users {
+        pool.get_snapshot_difference(followers_snapshot.id, friends_snapshot.id)
+            .await
+    } else {
+        pool.get_snapshot_difference(friends_snapshot.id, followers_snapshot.id)
+            .await
+    }
+}
 
-    let followers = BTreeSet::from_iter(followers.into_iter());
-    let friends = BTreeSet::from_iter(friends.into_iter());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::InMemoryPool;
+    use crate::test_utils::put_snapshot;
 
-    let result = if get_unfollowed_users {
-        followers
-            .into_iter()
-            .filter(|follower| !friends.contains(follower))
-            .collect()
-    } else {
-        friends
-            .into_iter()
-            .filter(|friend| !followers.contains(friend))
-            .collect()
-    };
-    Ok(result)
+    #[actix::test]
+    async fn test_get_difference() {
+        let pool = InMemoryPool::default();
+        put_snapshot(&pool, "1", true, &[1, 2, 3]).await.unwrap();
+        assert!(get_difference(&pool, "1", true).await.unwrap().is_empty());
+
+        put_snapshot(&pool, "1", false, &[2, 3, 4, 5])
+            .await
+            .unwrap();
+        put_snapshot(&pool, "2", false, &[1]).await.unwrap();
+        assert_eq!(get_difference(&pool, "1", true).await.unwrap(), vec![1]);
+        assert_eq!(get_difference(&pool, "1", false).await.unwrap(), vec![4, 5]);
+    }
+
+    #[actix::test]
+    async fn test_get_difference_uses_latest_snapshots() {
+        let pool = InMemoryPool::default();
+        put_snapshot(&pool, "1", true, &[1, 2]).await.unwrap();
+        put_snapshot(&pool, "1", false, &[2, 3]).await.unwrap();
+        put_snapshot(&pool, "1", true, &[2, 3]).await.unwrap();
+        // Unfinished snapshots are ignored.
+        let id = pool.start_snapshot("1", false, 0).await.unwrap();
+        pool.put_snapshot_members(id, &[1]).await.unwrap();
+
+        assert!(get_difference(&pool, "1", true).await.unwrap().is_empty());
+        assert!(get_difference(&pool, "1", false).await.unwrap().is_empty());
+    }
 }
@@ -0,0 +1,303 @@
+use egg_mode::user::TwitterUser;
+use serde::{Deserialize, Serialize};
+
+const SECONDS_PER_DAY: f64 = 3600.0 * 24.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Rule {
+    pub name: String,
+    pub condition: Condition,
+}
+
+/// A predicate on a `TwitterUser`, written in the config file as e.g.
+/// `{"all": [{"friends_count": {"max": 0}}, {"last_status_age_days": {"min": 730}}]}`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+    FollowersCount(Range),
+    FriendsCount(Range),
+    FollowersFriendsRatio(Range),
+    StatusesCount(Range),
+    Protected(bool),
+    Verified(bool),
+    DefaultProfileImage(bool),
+    NameContains(Vec<String>),
+    DescriptionContains(Vec<String>),
+    AccountAgeDays(Range),
+    LastStatusAgeDays(Range),
+}
+
+/// An inclusive range; a missing bound is unbounded.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Range {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl Range {
+    fn contains(&self, value: f64) -> bool {
+        self.min.is_none_or(|min| min <= value) && self.max.is_none_or(|max| value <= max)
+    }
+}
+
+impl Condition {
+    pub fn matches(&self, user: &TwitterUser, now: i64) -> bool {
+        match self {
+            Condition::All(conditions) => conditions.iter().all(|c| c.matches(user, now)),
+            Condition::Any(conditions) => conditions.iter().any(|c| c.matches(user, now)),
+            Condition::Not(condition) => !condition.matches(user, now),
+            Condition::FollowersCount(range) => range.contains(user.followers_count as f64),
+            Condition::FriendsCount(range) => range.contains(user.friends_count as f64),
+            Condition::FollowersFriendsRatio(range) => {
+                let ratio = if user.friends_count == 0 {
+                    f64::INFINITY
+                } else {
+                    user.followers_count as f64 / user.friends_count as f64
+                };
+                range.contains(ratio)
+            }
+            Condition::StatusesCount(range) => range.contains(user.statuses_count as f64),
+            Condition::Protected(protected) => user.protected == *protected,
+            Condition::Verified(verified) => user.verified == *verified,
+            Condition::DefaultProfileImage(default) => user.default_profile_image == *default,
+            Condition::NameContains(keywords) => {
+                contains_any(&user.name, keywords) || contains_any(&user.screen_name, keywords)
+            }
+            Condition::DescriptionContains(keywords) => {
+                let description = user.description.as_deref().unwrap_or("");
+                contains_any(description, keywords)
+            }
+            Condition::AccountAgeDays(range) => {
+                range.contains(age_days(user.created_at.timestamp(), now))
+            }
+            Condition::LastStatusAgeDays(range) => {
+                // A user who has never tweeted is treated as infinitely inactive.
+                let age = user
+                    .status
+                    .as_ref()
+                    .map(|status| age_days(status.created_at.timestamp(), now))
+                    .unwrap_or(f64::INFINITY);
+                range.contains(age)
+            }
+        }
+    }
+}
+
+/// Returns the first rule matching `user`.
+pub fn find_matching_rule<'a>(rules: &'a [Rule], user: &TwitterUser, now: i64) -> Option<&'a Rule> {
+    rules.iter().find(|rule| rule.condition.matches(user, now))
+}
+
+/// The rule `InvalidUserRemover` applies when no rules are configured: no friends and no tweet
+/// within two years.
+pub fn default_invalid_user_rules() -> Vec<Rule> {
+    vec![Rule {
+        name: "no_friends_and_no_tweet_in_two_years".to_string(),
+        condition: Condition::All(vec![
+            Condition::FriendsCount(Range {
+                min: None,
+                max: Some(0.0),
+            }),
+            Condition::LastStatusAgeDays(Range {
+                min: Some(365.0 * 2.0),
+                max: None,
+            }),
+        ]),
+    }]
+}
+
+fn contains_any(text: &str, keywords: &[String]) -> bool {
+    let text = text.to_lowercase();
+    keywords
+        .iter()
+        .any(|keyword| text.contains(&keyword.to_lowercase()))
+}
+
+fn age_days(timestamp: i64, now: i64) -> f64 {
+    (now - timestamp) as f64 / SECONDS_PER_DAY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::user;
+    use serde_json::json;
+
+    /// 2021-01-01, a bit over two years after the fixture user was created.
+    const NOW: i64 = 1_609_459_200;
+
+    fn condition(value: serde_json::Value) -> Condition {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn tweeted(user: &mut TwitterUser, created_at: &str) {
+        let status = json!({
+            "created_at": created_at,
+            "id": 1,
+            "id_str": "1",
+            "full_text": "hello",
+            "display_text_range": [0, 5],
+            "entities": {"hashtags": [], "symbols": [], "urls": [], "user_mentions": []},
+            "favorite_count": 0,
+            "retweet_count": 0,
+            "source": "<a href=\"https://example.com\">web</a>",
+            "truncated": false,
+            "lang": "en"
+        });
+        user.status = Some(Box::new(serde_json::from_value(status).unwrap()));
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut alice = user(10, "alice");
+        alice.friends_count = 5;
+        let cases = [
+            (json!({"followers_count": {"min": 10}}), true),
+            (json!({"followers_count": {"min": 11}}), false),
+            (json!({"friends_count": {"max": 5}}), true),
+            (json!({"friends_count": {"max": 4}}), false),
+            (json!({"friends_count": {}}), true),
+            (
+                json!({"followers_friends_ratio": {"min": 2, "max": 2}}),
+                true,
+            ),
+            (json!({"followers_friends_ratio": {"max": 1}}), false),
+            (json!({"statuses_count": {"max": 0}}), true),
+            (json!({"statuses_count": {"min": 1}}), false),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                condition(value.clone()).matches(&alice, NOW),
+                expected,
+                "{}",
+                value
+            );
+        }
+
+        // Users without friends have an infinite ratio.
+        let bob = user(11, "bob");
+        assert!(condition(json!({"followers_friends_ratio": {"min": 1000}})).matches(&bob, NOW));
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut alice = user(10, "alice");
+        alice.protected = true;
+        let cases = [
+            (json!({"protected": true}), true),
+            (json!({"protected": false}), false),
+            (json!({"verified": false}), true),
+            (json!({"verified": true}), false),
+            (json!({"default_profile_image": false}), true),
+            (json!({"default_profile_image": true}), false),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                condition(value.clone()).matches(&alice, NOW),
+                expected,
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_text() {
+        let mut alice = user(10, "alice_shop");
+        alice.name = "Alice".to_string();
+        let cases = [
+            (json!({"name_contains": ["ALICE"]}), true),
+            (json!({"name_contains": ["shop"]}), true),
+            (json!({"name_contains": ["bob", "carol"]}), false),
+            (json!({"name_contains": []}), false),
+            // The fixture has no description.
+            (json!({"description_contains": ["alice"]}), false),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                condition(value.clone()).matches(&alice, NOW),
+                expected,
+                "{}",
+                value
+            );
+        }
+
+        alice.description = Some("Follow back 100%".to_string());
+        assert!(condition(json!({"description_contains": ["follow back"]})).matches(&alice, NOW));
+    }
+
+    #[test]
+    fn test_ages() {
+        let mut alice = user(10, "alice");
+        let cases = [
+            (json!({"account_age_days": {"min": 730}}), true),
+            (json!({"account_age_days": {"min": 900}}), false),
+            // Users who have never tweeted are infinitely inactive.
+            (json!({"last_status_age_days": {"min": 100000}}), true),
+            (json!({"last_status_age_days": {"max": 30}}), false),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                condition(value.clone()).matches(&alice, NOW),
+                expected,
+                "{}",
+                value
+            );
+        }
+
+        tweeted(&mut alice, "Fri Dec 25 00:00:00 +0000 2020");
+        assert!(
+            condition(json!({"last_status_age_days": {"min": 7, "max": 7}})).matches(&alice, NOW)
+        );
+        assert!(!condition(json!({"last_status_age_days": {"min": 30}})).matches(&alice, NOW));
+    }
+
+    #[test]
+    fn test_combinators() {
+        let alice = user(10, "alice");
+        let yes = json!({"protected": false});
+        let no = json!({"protected": true});
+        let cases = [
+            (json!({"all": [yes, yes]}), true),
+            (json!({"all": [yes, no]}), false),
+            (json!({"all": []}), true),
+            (json!({"any": [no, yes]}), true),
+            (json!({"any": [no, no]}), false),
+            (json!({"any": []}), false),
+            (json!({"not": no}), true),
+            (json!({"not": {"any": [yes]}}), false),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                condition(value.clone()).matches(&alice, NOW),
+                expected,
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_matching_rule() {
+        let rule = |name: &str, value| Rule {
+            name: name.to_string(),
+            condition: condition(value),
+        };
+        let rules = vec![
+            rule("verified", json!({"verified": true})),
+            rule("no_friends", json!({"friends_count": {"max": 0}})),
+            rule("no_tweets", json!({"statuses_count": {"max": 0}})),
+        ];
+        let alice = user(10, "alice");
+        let matched = find_matching_rule(&rules, &alice, NOW).unwrap();
+        assert_eq!(matched.name, "no_friends");
+        assert!(find_matching_rule(&rules[..1], &alice, NOW).is_none());
+
+        let default_rules = default_invalid_user_rules();
+        let matched = find_matching_rule(&default_rules, &alice, NOW).unwrap();
+        assert_eq!(matched.name, "no_friends_and_no_tweet_in_two_years");
+    }
+}
@@ -1,15 +1,21 @@
-use crate::sql::PgPoolExt;
-use crate::twitter::{RelationLookupExt, TwitterClient};
+use crate::action::{enqueue_action, perform_action, ActionKind, Actor};
+use crate::rules::{Condition, Range};
+use crate::sql::{
+    ActionFilter, ActionLogClient, ActionQueueClient, AllowedUser, AllowlistClient, BlockedUser,
+    BlocklistClient, DeferredUser, DeferredUserClient, PgPoolExt, QueueFilter,
+    RelationshipEventClient, RelationshipEventFilter, RemoveJob, RemoveJobClient, RemoveJobUser,
+    SkippedUserClient, SnapshotClient, UserHistoryClient,
+};
+use crate::twitter::{Priority, RelationLookupExt, TwitterApi, TwitterError};
 use crate::{current_time_duration, get_difference};
-use actix_web::web::{Data, Json, Path};
-use actix_web::{get, post, HttpResponse, ResponseError};
+use actix_web::http::StatusCode;
+use actix_web::web::{self, Data, Json, Path, Query, ServiceConfig};
+use actix_web::{HttpResponse, ResponseError};
 use anyhow::Error;
-use egg_mode::user::unfollow;
-use rand::prelude::*;
-use rand::SeedableRng;
+use egg_mode::user::TwitterUser;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
-use std::collections::BTreeMap;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Display, Formatter};
 
 #[derive(Debug)]
@@ -19,41 +25,301 @@ impl Display for ActixError {
         write!(f, "{:?}", self.0)
     }
 }
-impl ResponseError for ActixError {}
+impl ResponseError for ActixError {
+    fn status_code(&self) -> StatusCode {
+        if let Some(TwitterError::RateLimit { .. }) = self.0.downcast_ref::<TwitterError>() {
+            return StatusCode::TOO_MANY_REQUESTS;
+        }
+        match self.0.downcast_ref::<RequestError>() {
+            Some(RequestError::NotFound(_)) => StatusCode::NOT_FOUND,
+            Some(RequestError::BadRequest(_)) => StatusCode::BAD_REQUEST,
+            None => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
 impl From<Error> for ActixError {
     fn from(e: Error) -> Self {
         Self(e)
     }
 }
 
-#[get("/remove_candidates")]
-pub async fn get_remove_candidates(
-    pool: Data<PgPool>,
-    client: Data<TwitterClient>,
+#[derive(Debug)]
+pub enum RequestError {
+    NotFound(String),
+    BadRequest(String),
+}
+impl Display for RequestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            RequestError::NotFound(message) => write!(f, "Not found: {}", message),
+            RequestError::BadRequest(message) => write!(f, "Bad request: {}", message),
+        }
+    }
+}
+impl std::error::Error for RequestError {}
+
+/// Twitter clients of the managed accounts, keyed by `TwitterApi::account`.
+pub type Accounts<T> = BTreeMap<String, T>;
+
+/// Whether follows and unfollows are only recorded in the action log, as for the workers.
+#[derive(Clone, Copy)]
+pub struct DryRun(pub bool);
+
+fn find_account<'a, T>(accounts: &'a Accounts<T>, account: &str) -> Result<&'a T, ActixError> {
+    accounts.get(account).ok_or_else(|| {
+        let e = RequestError::NotFound(format!("account {}", account));
+        ActixError(e.into())
+    })
+}
+
+pub fn config<P, T>(cfg: &mut ServiceConfig)
+where
+    P: PgPoolExt
+        + ActionLogClient
+        + BlocklistClient
+        + SkippedUserClient
+        + RelationshipEventClient
+        + SnapshotClient
+        + UserHistoryClient
+        + ActionQueueClient
+        + RemoveJobClient
+        + AllowlistClient
+        + DeferredUserClient
+        + 'static,
+    T: TwitterApi + 'static,
+{
+    cfg.route("/accounts", web::get().to(get_accounts::<T>))
+        .route(
+            "/users/{user_id}/history",
+            web::get().to(get_user_history::<P>),
+        )
+        .service(
+            web::scope("/accounts/{account}")
+                .route(
+                    "/remove_candidates",
+                    web::get().to(get_remove_candidates::<P, T>),
+                )
+                .route("/user_info/{user_id}", web::get().to(get_user_info::<T>))
+                .route("/remove_user", web::post().to(remove_user::<P, T>))
+                .route("/remove_users", web::post().to(remove_users::<P, T>))
+                .route("/remove_jobs/{id}", web::get().to(get_remove_job::<P, T>))
+                .route("/defer_user", web::post().to(defer_user::<P, T>))
+                .route("/actions", web::get().to(get_actions::<P, T>))
+                .route(
+                    "/relationship_events",
+                    web::get().to(get_relationship_events::<P, T>),
+                )
+                .route("/blocklist", web::get().to(get_blocklist::<P, T>))
+                .route("/blocklist", web::post().to(add_to_blocklist::<P, T>))
+                .route(
+                    "/blocklist/{user_id}",
+                    web::delete().to(remove_from_blocklist::<P, T>),
+                )
+                .route("/allowlist", web::get().to(get_allowlist::<P, T>))
+                .route("/allowlist", web::post().to(add_to_allowlist::<P, T>))
+                .route(
+                    "/allowlist/{user_id}",
+                    web::delete().to(remove_from_allowlist::<P, T>),
+                )
+                .route(
+                    "/follow_back_skips",
+                    web::get().to(get_follow_back_skips::<P, T>),
+                )
+                .route("/queue", web::get().to(get_queue::<P, T>))
+                .route(
+                    "/queue/{id}",
+                    web::delete().to(cancel_queued_action::<P, T>),
+                ),
+        );
+}
+
+#[derive(Serialize)]
+pub struct AccountResponse<'a> {
+    account: &'a str,
+    screen_name: &'a str,
+}
+
+pub async fn get_accounts<T: TwitterApi>(
+    accounts: Data<Accounts<T>>,
 ) -> Result<HttpResponse, ActixError> {
-    let one_hour_ago = current_time_duration().as_secs() - 3600;
-    let mut rng = StdRng::seed_from_u64(one_hour_ago);
-    let mut remove_candidate_ids =
-        get_difference(pool.as_ref(), one_hour_ago as i64, false).await?;
-    remove_candidate_ids.shuffle(&mut rng);
+    let accounts = accounts
+        .values()
+        .map(|client| AccountResponse {
+            account: client.account(),
+            screen_name: client.screen_name(),
+        })
+        .collect::<Vec<_>>();
+    Ok(HttpResponse::Ok().json(accounts))
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CandidateSort {
+    /// Users who have never tweeted come first in ascending order.
+    #[default]
+    LastTweet,
+    FollowersCount,
+    FollowDuration,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct CandidateQuery {
+    sort: CandidateSort,
+    order: SortOrder,
+    protected: Option<bool>,
+    verified: Option<bool>,
+    /// Only users who have not tweeted for at least this many days.
+    inactive_days: Option<f64>,
+    /// `next_cursor` of the previous page, with the same sort and order.
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
 
-    let mut user_data = vec![];
-    for user_id in remove_candidate_ids {
-        if let Some(user) = pool.get_user_info(user_id).await? {
-            user_data.push(user);
+impl CandidateQuery {
+    fn condition(&self) -> Condition {
+        let mut conditions = vec![];
+        if let Some(protected) = self.protected {
+            conditions.push(Condition::Protected(protected));
         }
+        if let Some(verified) = self.verified {
+            conditions.push(Condition::Verified(verified));
+        }
+        if let Some(days) = self.inactive_days {
+            conditions.push(Condition::LastStatusAgeDays(Range {
+                min: Some(days),
+                max: None,
+            }));
+        }
+        Condition::All(conditions)
+    }
+
+    /// Sorting key in ascending order. Users followed before relationship events were recorded
+    /// count as followed the longest.
+    fn sort_key(&self, user: &TwitterUser, followed_at: Option<i64>) -> i64 {
+        match self.sort {
+            CandidateSort::LastTweet => user
+                .status
+                .as_ref()
+                .map(|status| status.created_at.timestamp())
+                .unwrap_or(0),
+            CandidateSort::FollowersCount => user.followers_count as i64,
+            CandidateSort::FollowDuration => -followed_at.unwrap_or(0),
+        }
+    }
+
+    fn parse_cursor(&self) -> Result<Option<(i64, u64)>, ActixError> {
+        let cursor = match self.cursor.as_deref() {
+            Some(cursor) => cursor,
+            None => return Ok(None),
+        };
+        let parsed = cursor
+            .rsplit_once('_')
+            .and_then(|(key, user_id)| Some((key.parse().ok()?, user_id.parse().ok()?)));
+        match parsed {
+            Some(cursor) => Ok(Some(cursor)),
+            None => {
+                let e = RequestError::BadRequest(format!("invalid cursor {}", cursor));
+                Err(ActixError(e.into()))
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RemoveCandidatesResponse {
+    users: Vec<TwitterUser>,
+    /// Number of candidates matching the filters, before the live relations are checked.
+    total: usize,
+    next_cursor: Option<String>,
+}
+
+pub async fn get_remove_candidates<
+    P: PgPoolExt + SnapshotClient + RelationshipEventClient + AllowlistClient + DeferredUserClient,
+    T: TwitterApi,
+>(
+    path: Path<String>,
+    query: Query<CandidateQuery>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let client = find_account(&accounts, &path)?;
+    let account = client.account();
+    let now = current_time_duration().as_secs() as i64;
+    // A page is checked against the live relations in one lookup of at most 100 users.
+    let limit = query.limit.unwrap_or(100).clamp(1, 100) as usize;
+    let cursor = query.parse_cursor()?;
+
+    let mut excluded_ids = pool
+        .get_allowed_users(account)
+        .await?
+        .into_iter()
+        .map(|user| user.user_id)
+        .collect::<BTreeSet<_>>();
+    excluded_ids.extend(pool.get_deferred_user_ids(account, now).await?);
+    let mut candidate_ids = get_difference(pool.as_ref(), account, false).await?;
+    candidate_ids.retain(|user_id| !excluded_ids.contains(user_id));
+    let users = pool.get_user_infos(&candidate_ids).await?;
+    let followed_at = pool
+        .get_relationship_started_at(account, false, &candidate_ids)
+        .await?;
+    let condition = query.condition();
+    let mut candidates = users
+        .into_values()
+        .filter(|user| condition.matches(user, now))
+        .map(|user| {
+            let key = query.sort_key(&user, followed_at.get(&(user.id as i64)).copied());
+            ((key, user.id), user)
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|(key, _)| *key);
+    if query.order == SortOrder::Desc {
+        candidates.reverse();
     }
+    let total = candidates.len();
+
+    let start = match cursor {
+        Some(cursor) => candidates.partition_point(|(key, _)| match query.order {
+            SortOrder::Asc => *key <= cursor,
+            SortOrder::Desc => *key >= cursor,
+        }),
+        None => 0,
+    };
+    let page = candidates
+        .into_iter()
+        .skip(start)
+        .take(limit + 1)
+        .collect::<Vec<_>>();
+    let next_cursor = if page.len() > limit {
+        page.get(limit - 1)
+            .map(|((key, user_id), _)| format!("{}_{}", key, user_id))
+    } else {
+        None
+    };
+    let user_data = page
+        .into_iter()
+        .take(limit)
+        .map(|(_, user)| user)
+        .collect::<Vec<_>>();
 
-    user_data.shuffle(&mut rng);
-    user_data.truncate(100);
     let user_ids = user_data.iter().map(|user| user.id).collect::<Vec<_>>();
-    let relations = client.get_relations(&user_ids, false).await?;
+    let relations = client
+        .get_relations(&user_ids, Priority::Interactive)
+        .await?;
     let mut relation_map = BTreeMap::new();
     for relation in relations {
         relation_map.insert(relation.id, relation);
     }
 
-    let user_data = user_data
+    let users = user_data
         .into_iter()
         .filter(|user| {
             if let Some(relation) = relation_map.get(&user.id) {
@@ -64,17 +330,96 @@ pub async fn get_remove_candidates(
         })
         .collect::<Vec<_>>();
 
+    Ok(HttpResponse::Ok().json(RemoveCandidatesResponse {
+        users,
+        total,
+        next_cursor,
+    }))
+}
+
+pub async fn get_user_info<T: TwitterApi>(
+    path: Path<(String, u64)>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let (account, user_id) = path.into_inner();
+    let client = find_account(&accounts, &account)?;
+    let user_data = client
+        .get_user_data(&[user_id], Priority::Interactive)
+        .await?;
     Ok(HttpResponse::Ok().json(user_data))
 }
 
-#[get("/user_info/{user_id}")]
-pub async fn get_user_info(
-    path: Path<u64>,
-    client: Data<TwitterClient>,
+#[derive(Serialize)]
+pub struct FieldChange {
+    field: String,
+    old: Value,
+    new: Value,
+}
+
+#[derive(Serialize)]
+pub struct ProfileDiff {
+    id: i64,
+    fetched_at: i64,
+    changes: Vec<FieldChange>,
+}
+
+#[derive(Serialize)]
+pub struct UserHistoryResponse {
+    user_id: i64,
+    first_fetched_at: i64,
+    diffs: Vec<ProfileDiff>,
+}
+
+pub async fn get_user_history<P: UserHistoryClient>(
+    path: Path<i64>,
+    pool: Data<P>,
 ) -> Result<HttpResponse, ActixError> {
     let user_id = path.into_inner();
-    let user_data = client.get_user_data(&vec![user_id], false).await?;
-    Ok(HttpResponse::Ok().json(user_data))
+    let history = pool.get_user_history(user_id).await?;
+    let first_fetched_at = match history.first() {
+        Some(first) => first.fetched_at,
+        None => {
+            let e = RequestError::NotFound(format!("history of user {}", user_id));
+            return Err(ActixError(e.into()));
+        }
+    };
+    let diffs = history
+        .windows(2)
+        .map(|versions| ProfileDiff {
+            id: versions[1].id,
+            fetched_at: versions[1].fetched_at,
+            changes: diff_fields(&versions[0].data, &versions[1].data),
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(UserHistoryResponse {
+        user_id,
+        first_fetched_at,
+        diffs,
+    }))
+}
+
+/// Top-level fields whose values differ between two profiles.
+fn diff_fields(old: &Value, new: &Value) -> Vec<FieldChange> {
+    let empty = serde_json::Map::new();
+    let old = old.as_object().unwrap_or(&empty);
+    let new = new.as_object().unwrap_or(&empty);
+    let fields = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let old = old.get(field).cloned().unwrap_or(Value::Null);
+            let new = new.get(field).cloned().unwrap_or(Value::Null);
+            if old == new {
+                None
+            } else {
+                Some(FieldChange {
+                    field: field.clone(),
+                    old,
+                    new,
+                })
+            }
+        })
+        .collect()
 }
 
 #[derive(Serialize, Deserialize)]
@@ -82,15 +427,940 @@ pub struct RemoveRequest {
     user_id: i64,
 }
 
-#[post("/remove_user")]
-pub async fn remove_user(
+pub async fn remove_user<P: ActionLogClient + BlocklistClient + AllowlistClient, T: TwitterApi>(
+    path: Path<String>,
     request: Json<RemoveRequest>,
-    client: Data<TwitterClient>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+    dry_run: Data<DryRun>,
+) -> Result<HttpResponse, ActixError> {
+    let client = find_account(&accounts, &path)?;
+    log::info!("@{} is removing {}", client.screen_name(), request.user_id);
+    let user = perform_action(
+        pool.as_ref(),
+        client,
+        ActionKind::Unfollow,
+        request.user_id as u64,
+        Actor::RemoveUserHandler,
+        "removed manually",
+        dry_run.0,
+    )
+    .await?;
+    pool.put_blocked_user(
+        client.account(),
+        &BlockedUser {
+            user_id: request.user_id,
+            reason: "removed manually".to_string(),
+            created_at: current_time_duration().as_secs() as i64,
+        },
+    )
+    .await?;
+    if let Some(user) = user.as_ref() {
+        log::info!("Removed @{}", user.screen_name);
+    }
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RemoveUsersRequest {
+    user_ids: Vec<i64>,
+}
+
+#[derive(Serialize)]
+pub struct RemoveJobUserStatus {
+    user_id: i64,
+    /// `skipped`, or the status of the queued unfollow.
+    status: String,
+    /// Why the user was skipped, or the last error of the unfollow.
+    error: Option<String>,
+    attempts: i32,
+}
+
+#[derive(Serialize)]
+pub struct RemoveJobResponse {
+    id: i64,
+    created_at: i64,
+    /// Number of users in each status.
+    progress: BTreeMap<String, usize>,
+    users: Vec<RemoveJobUserStatus>,
+}
+
+/// Queues unfollows of the users the account follows, as one job whose progress is reported by
+/// `get_remove_job`. Like `remove_user`, the users are added to the blocklist. Users on the
+/// allowlist are skipped, in case the list was shown before they were added.
+pub async fn remove_users<P, T>(
+    path: Path<String>,
+    request: Json<RemoveUsersRequest>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError>
+where
+    P: ActionQueueClient + AllowlistClient + BlocklistClient + RemoveJobClient,
+    T: TwitterApi,
+{
+    let client = find_account(&accounts, &path)?;
+    let account = client.account();
+    let user_ids = request.user_ids.iter().copied().collect::<BTreeSet<_>>();
+    if user_ids.is_empty() || user_ids.len() > 100 {
+        let e = RequestError::BadRequest("user_ids must have 1 to 100 users".to_string());
+        return Err(ActixError(e.into()));
+    }
+
+    let ids = user_ids.iter().map(|&id| id as u64).collect::<Vec<_>>();
+    let friends = client
+        .get_relations(&ids, Priority::Interactive)
+        .await?
+        .into_iter()
+        .filter(|relation| relation.is_friend())
+        .map(|relation| relation.id as i64)
+        .collect::<BTreeSet<_>>();
+    let allowed_ids = pool
+        .get_allowed_users(account)
+        .await?
+        .into_iter()
+        .map(|user| user.user_id)
+        .collect::<BTreeSet<_>>();
+
+    let now = current_time_duration().as_secs() as i64;
+    let mut users = vec![];
+    for user_id in user_ids {
+        let skipped_reason = if !friends.contains(&user_id) {
+            Some("not_following")
+        } else if allowed_ids.contains(&user_id) {
+            Some("allowlisted")
+        } else {
+            None
+        };
+        if let Some(skipped_reason) = skipped_reason {
+            users.push(RemoveJobUser {
+                user_id,
+                action_id: None,
+                skipped_reason: Some(skipped_reason.to_string()),
+            });
+            continue;
+        }
+        pool.put_blocked_user(
+            account,
+            &BlockedUser {
+                user_id,
+                reason: "removed manually".to_string(),
+                created_at: now,
+            },
+        )
+        .await?;
+        // The unfollow may have been queued already, e.g. by `InvalidUserRemover`.
+        let action = enqueue_action(
+            pool.as_ref(),
+            account,
+            ActionKind::Unfollow,
+            user_id as u64,
+            Actor::RemoveUserHandler,
+            "removed manually",
+        )
+        .await?;
+        users.push(RemoveJobUser {
+            user_id,
+            action_id: Some(action.id),
+            skipped_reason: None,
+        });
+    }
+    let id = pool.put_remove_job(account, &users, now).await?;
+    log::info!("@{} queued remove job {}", client.screen_name(), id);
+
+    let job = RemoveJob {
+        id,
+        account: account.to_string(),
+        created_at: now,
+        users,
+    };
+    Ok(HttpResponse::Ok().json(remove_job_response(pool.as_ref(), job).await?))
+}
+
+pub async fn get_remove_job<P: ActionQueueClient + RemoveJobClient, T: TwitterApi>(
+    path: Path<(String, i64)>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let (account, id) = path.into_inner();
+    let client = find_account(&accounts, &account)?;
+    match pool.get_remove_job(client.account(), id).await? {
+        Some(job) => Ok(HttpResponse::Ok().json(remove_job_response(pool.as_ref(), job).await?)),
+        None => {
+            let e = RequestError::NotFound(format!("remove job {}", id));
+            Err(ActixError(e.into()))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DeferRequest {
+    user_id: i64,
+    days: i64,
+}
+
+/// Hides the user from the remove candidates, and keeps the workers from queueing actions on
+/// them, for the given number of days.
+pub async fn defer_user<P: DeferredUserClient, T: TwitterApi>(
+    path: Path<String>,
+    request: Json<DeferRequest>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
 ) -> Result<HttpResponse, ActixError> {
-    log::info!("Removing {}", request.user_id);
-    let result = unfollow(request.user_id as u64, &client.token)
+    let client = find_account(&accounts, &path)?;
+    let request = request.into_inner();
+    if !(1..=365).contains(&request.days) {
+        let e = RequestError::BadRequest(format!("days must be 1 to 365, got {}", request.days));
+        return Err(ActixError(e.into()));
+    }
+    let now = current_time_duration().as_secs() as i64;
+    let user = DeferredUser {
+        user_id: request.user_id,
+        until: now + request.days * 24 * 3600,
+        created_at: now,
+    };
+    pool.put_deferred_user(client.account(), &user).await?;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+async fn remove_job_response<P: ActionQueueClient>(
+    pool: &P,
+    job: RemoveJob,
+) -> anyhow::Result<RemoveJobResponse> {
+    let action_ids = job
+        .users
+        .iter()
+        .filter_map(|user| user.action_id)
+        .collect::<Vec<_>>();
+    let actions = pool
+        .get_queued_actions_by_ids(&action_ids)
+        .await?
+        .into_iter()
+        .map(|action| (action.id, action))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut progress = BTreeMap::new();
+    let users = job
+        .users
+        .into_iter()
+        .map(|user| {
+            let status = match user.action_id.and_then(|id| actions.get(&id)) {
+                Some(action) => RemoveJobUserStatus {
+                    user_id: user.user_id,
+                    status: action.status.as_str().to_string(),
+                    error: action.last_error.clone(),
+                    attempts: action.attempts,
+                },
+                None => RemoveJobUserStatus {
+                    user_id: user.user_id,
+                    status: "skipped".to_string(),
+                    error: user.skipped_reason,
+                    attempts: 0,
+                },
+            };
+            *progress.entry(status.status.clone()).or_insert(0) += 1;
+            status
+        })
+        .collect();
+    Ok(RemoveJobResponse {
+        id: job.id,
+        created_at: job.created_at,
+        progress,
+        users,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct PageQuery {
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct ActionsResponse<A> {
+    actions: Vec<A>,
+    next_before_id: Option<i64>,
+}
+
+pub async fn get_actions<P: ActionLogClient, T: TwitterApi>(
+    path: Path<String>,
+    filter: Query<ActionFilter>,
+    page: Query<PageQuery>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let client = find_account(&accounts, &path)?;
+    let limit = page.limit.unwrap_or(100).clamp(1, 1000);
+    let actions = pool.get_actions(client.account(), &filter, limit).await?;
+    let next_before_id = if actions.len() as i64 == limit {
+        actions.last().map(|action| action.id)
+    } else {
+        None
+    };
+    Ok(HttpResponse::Ok().json(ActionsResponse {
+        actions,
+        next_before_id,
+    }))
+}
+
+#[derive(Serialize)]
+pub struct RelationshipEventsResponse<E> {
+    events: Vec<E>,
+    next_before_id: Option<i64>,
+}
+
+pub async fn get_relationship_events<P: RelationshipEventClient, T: TwitterApi>(
+    path: Path<String>,
+    filter: Query<RelationshipEventFilter>,
+    page: Query<PageQuery>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let client = find_account(&accounts, &path)?;
+    let limit = page.limit.unwrap_or(100).clamp(1, 1000);
+    let events = pool
+        .get_relationship_events(client.account(), &filter, limit)
+        .await?;
+    let next_before_id = if events.len() as i64 == limit {
+        events.last().map(|event| event.id)
+    } else {
+        None
+    };
+    Ok(HttpResponse::Ok().json(RelationshipEventsResponse {
+        events,
+        next_before_id,
+    }))
+}
+
+pub async fn get_blocklist<P: BlocklistClient, T: TwitterApi>(
+    path: Path<String>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let client = find_account(&accounts, &path)?;
+    let users = pool.get_blocked_users(client.account()).await?;
+    Ok(HttpResponse::Ok().json(users))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BlockRequest {
+    user_id: i64,
+    reason: Option<String>,
+}
+
+pub async fn add_to_blocklist<P: BlocklistClient, T: TwitterApi>(
+    path: Path<String>,
+    request: Json<BlockRequest>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let client = find_account(&accounts, &path)?;
+    let request = request.into_inner();
+    let user = BlockedUser {
+        user_id: request.user_id,
+        reason: request
+            .reason
+            .unwrap_or_else(|| "added manually".to_string()),
+        created_at: current_time_duration().as_secs() as i64,
+    };
+    pool.put_blocked_user(client.account(), &user).await?;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+pub async fn remove_from_blocklist<P: BlocklistClient, T: TwitterApi>(
+    path: Path<(String, i64)>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let (account, user_id) = path.into_inner();
+    let client = find_account(&accounts, &account)?;
+    if pool.delete_blocked_user(client.account(), user_id).await? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        let e = RequestError::NotFound(format!("user {} in the blocklist", user_id));
+        Err(ActixError(e.into()))
+    }
+}
+
+pub async fn get_allowlist<P: AllowlistClient, T: TwitterApi>(
+    path: Path<String>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let client = find_account(&accounts, &path)?;
+    let users = pool.get_allowed_users(client.account()).await?;
+    Ok(HttpResponse::Ok().json(users))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AllowRequest {
+    user_id: i64,
+    reason: Option<String>,
+}
+
+/// Also cancels the queued unfollow of the user, if any.
+pub async fn add_to_allowlist<P: ActionQueueClient + AllowlistClient, T: TwitterApi>(
+    path: Path<String>,
+    request: Json<AllowRequest>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let client = find_account(&accounts, &path)?;
+    let request = request.into_inner();
+    let user = AllowedUser {
+        user_id: request.user_id,
+        reason: request
+            .reason
+            .unwrap_or_else(|| "added manually".to_string()),
+        created_at: current_time_duration().as_secs() as i64,
+    };
+    pool.put_allowed_user(client.account(), &user).await?;
+    if let Some(action) = pool
+        .get_open_queued_action(
+            client.account(),
+            ActionKind::Unfollow.as_str(),
+            user.user_id,
+        )
+        .await?
+    {
+        if pool
+            .cancel_queued_action(client.account(), action.id, user.created_at)
+            .await?
+        {
+            log::info!("Cancelled the queued unfollow of {}", user.user_id);
+        }
+    }
+    Ok(HttpResponse::Ok().json(user))
+}
+
+pub async fn remove_from_allowlist<P: AllowlistClient, T: TwitterApi>(
+    path: Path<(String, i64)>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let (account, user_id) = path.into_inner();
+    let client = find_account(&accounts, &account)?;
+    if pool.delete_allowed_user(client.account(), user_id).await? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        let e = RequestError::NotFound(format!("user {} in the allowlist", user_id));
+        Err(ActixError(e.into()))
+    }
+}
+
+pub async fn get_follow_back_skips<P: SkippedUserClient, T: TwitterApi>(
+    path: Path<String>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let client = find_account(&accounts, &path)?;
+    let users = pool.get_skipped_users(client.account()).await?;
+    Ok(HttpResponse::Ok().json(users))
+}
+
+#[derive(Serialize)]
+pub struct QueueResponse<A> {
+    actions: Vec<A>,
+    next_before_id: Option<i64>,
+}
+
+pub async fn get_queue<P: ActionQueueClient, T: TwitterApi>(
+    path: Path<String>,
+    filter: Query<QueueFilter>,
+    page: Query<PageQuery>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let client = find_account(&accounts, &path)?;
+    let limit = page.limit.unwrap_or(100).clamp(1, 1000);
+    let actions = pool
+        .get_queued_actions(client.account(), &filter, limit)
+        .await?;
+    let next_before_id = if actions.len() as i64 == limit {
+        actions.last().map(|action| action.id)
+    } else {
+        None
+    };
+    Ok(HttpResponse::Ok().json(QueueResponse {
+        actions,
+        next_before_id,
+    }))
+}
+
+pub async fn cancel_queued_action<P: ActionQueueClient, T: TwitterApi>(
+    path: Path<(String, i64)>,
+    pool: Data<P>,
+    accounts: Data<Accounts<T>>,
+) -> Result<HttpResponse, ActixError> {
+    let (account, id) = path.into_inner();
+    let client = find_account(&accounts, &account)?;
+    let now = current_time_duration().as_secs() as i64;
+    if pool.cancel_queued_action(client.account(), id, now).await? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        let e = RequestError::NotFound(format!("pending action {} in the queue", id));
+        Err(ActixError(e.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::{ActionFilter, InMemoryPool, NewAction, QueueFilter, QueueStatus};
+    use crate::test_utils::{put_snapshot, user};
+    use crate::twitter::FakeTwitterClient;
+    use crate::worker::{ActionScheduler, ActionSchedulerConfig};
+    use actix_web::{test, App};
+    use serde_json::json;
+
+    fn fake_client() -> FakeTwitterClient {
+        let client = FakeTwitterClient::new(1, "me");
+        for id in 10..=13 {
+            client.add_user(user(id, &format!("user{}", id)));
+        }
+        client
+    }
+
+    async fn respond(
+        pool: &InMemoryPool,
+        client: &FakeTwitterClient,
+        dry_run: bool,
+        request: test::TestRequest,
+    ) -> (StatusCode, Value) {
+        let accounts = vec![(client.account().to_string(), client.clone())]
+            .into_iter()
+            .collect::<Accounts<_>>();
+        let app = test::init_service(
+            App::new()
+                .configure(config::<InMemoryPool, FakeTwitterClient>)
+                .data(accounts)
+                .data(pool.clone())
+                .data(DryRun(dry_run)),
+        )
+        .await;
+        let response = test::call_service(&app, request.to_request()).await;
+        let status = response.status();
+        let body = test::read_body(response).await;
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    async fn call(
+        pool: &InMemoryPool,
+        client: &FakeTwitterClient,
+        dry_run: bool,
+        request: test::TestRequest,
+    ) -> Value {
+        let (status, body) = respond(pool, client, dry_run, request).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body
+    }
+
+    #[actix::test]
+    async fn test_get_accounts() {
+        let pool = InMemoryPool::default();
+        let response = call(
+            &pool,
+            &fake_client(),
+            false,
+            test::TestRequest::get().uri("/accounts"),
+        )
+        .await;
+        assert_eq!(response, json!([{"account": "1", "screen_name": "me"}]));
+    }
+
+    #[test]
+    fn test_error_status_code() {
+        let status_code = |e: Error| ActixError(e).status_code();
+        let rate_limit = TwitterError::RateLimit { reset: 0 };
+        assert_eq!(
+            status_code(rate_limit.into()),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        let not_found = RequestError::NotFound("user".to_string());
+        assert_eq!(status_code(not_found.into()), StatusCode::NOT_FOUND);
+        let other = TwitterError::Other("error".to_string());
+        assert_eq!(status_code(other.into()), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix::test]
+    async fn test_get_actions() {
+        let pool = InMemoryPool::default();
+        let client = fake_client();
+        let actions = [
+            ("1", "follow_back_worker", 10, "follow", "succeeded", 100),
+            (
+                "1",
+                "invalid_user_remover",
+                11,
+                "unfollow",
+                "succeeded",
+                200,
+            ),
+            ("1", "remove_user", 12, "unfollow", "failed", 300),
+            ("2", "remove_user", 13, "unfollow", "succeeded", 400),
+            ("1", "remove_user", 14, "unfollow", "dry_run", 500),
+        ];
+        for (account, actor, target_id, action, result, created_at) in actions {
+            pool.put_action(&NewAction {
+                account,
+                actor,
+                target_id,
+                action,
+                reason: "test",
+                result,
+                error: None,
+                created_at,
+            })
+            .await
+            .unwrap();
+        }
+        let get = |query: &str| {
+            let uri = format!("/accounts/1/actions?{}", query);
+            let pool = pool.clone();
+            let client = client.clone();
+            async move {
+                let response =
+                    call(&pool, &client, false, test::TestRequest::get().uri(&uri)).await;
+                let target_ids = response["actions"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|action| action["target_id"].as_i64().unwrap())
+                    .collect::<Vec<_>>();
+                (target_ids, response["next_before_id"].as_i64())
+            }
+        };
+
+        let cases = [
+            ("", vec![14, 12, 11, 10]),
+            ("action=follow", vec![10]),
+            ("actor=remove_user", vec![14, 12]),
+            ("result=succeeded", vec![11, 10]),
+            ("target_id=12", vec![12]),
+            ("since=200&until=500", vec![12, 11]),
+            ("action=unfollow&result=failed", vec![12]),
+        ];
+        for (query, expected) in cases {
+            assert_eq!(get(query).await.0, expected, "{}", query);
+        }
+
+        // Newest first, one page at a time.
+        let (page, next_before_id) = get("action=unfollow&limit=2").await;
+        assert_eq!(page, vec![14, 12]);
+        let next_before_id = next_before_id.unwrap();
+        let query = format!("action=unfollow&limit=2&before_id={}", next_before_id);
+        let (page, next_before_id) = get(&query).await;
+        assert_eq!(page, vec![11]);
+        assert_eq!(next_before_id, None);
+    }
+
+    /// Friends 10 to 14 who do not follow back, with followers counts 50, 20, 20, 5 and 30.
+    async fn candidates_fixture() -> (InMemoryPool, FakeTwitterClient) {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me");
+        for (id, followers_count) in [(10, 50), (11, 20), (12, 20), (13, 5), (14, 30)] {
+            let mut user = user(id, &format!("user{}", id));
+            user.followers_count = followers_count;
+            user.protected = id == 13;
+            user.verified = id == 14;
+            pool.put_user_info(&user).await.unwrap();
+            client.add_user(user);
+            client.add_friend(id);
+        }
+        put_snapshot(&pool, "1", true, &[]).await.unwrap();
+        put_snapshot(&pool, "1", false, &[10, 11, 12, 13, 14])
+            .await
+            .unwrap();
+        (pool, client)
+    }
+
+    async fn candidates(
+        pool: &InMemoryPool,
+        client: &FakeTwitterClient,
+        query: &str,
+    ) -> (Vec<u64>, usize, Option<String>) {
+        let uri = format!("/accounts/1/remove_candidates?{}", query);
+        let response = call(pool, client, false, test::TestRequest::get().uri(&uri)).await;
+        let user_ids = response["users"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|user| user["id"].as_u64().unwrap())
+            .collect();
+        let total = response["total"].as_u64().unwrap() as usize;
+        let next_cursor = response["next_cursor"].as_str().map(|s| s.to_string());
+        (user_ids, total, next_cursor)
+    }
+
+    #[actix::test]
+    async fn test_get_remove_candidates_paging() {
+        let (pool, client) = candidates_fixture().await;
+        let pages = [
+            ("asc", vec![vec![13, 11], vec![12, 14], vec![10]]),
+            ("desc", vec![vec![10, 14], vec![12, 11], vec![13]]),
+        ];
+        for (order, expected) in pages {
+            let mut cursor = None;
+            for (i, expected) in expected.into_iter().enumerate() {
+                let mut query = format!("sort=followers_count&order={}&limit=2", order);
+                if let Some(cursor) = &cursor {
+                    query.push_str(&format!("&cursor={}", cursor));
+                }
+                let (user_ids, total, next_cursor) = candidates(&pool, &client, &query).await;
+                assert_eq!(user_ids, expected, "{} page {}", order, i);
+                assert_eq!(total, 5);
+                cursor = next_cursor;
+            }
+            assert_eq!(cursor, None, "{}", order);
+        }
+
+        // Users who follow back are left out of the page, but not of the total.
+        client.add_follower(13);
+        let query = "sort=followers_count&limit=2";
+        let (user_ids, total, next_cursor) = candidates(&pool, &client, query).await;
+        assert_eq!(user_ids, vec![11]);
+        assert_eq!(total, 5);
+        assert_eq!(next_cursor.as_deref(), Some("20_11"));
+    }
+
+    #[actix::test]
+    async fn test_get_remove_candidates_filters() {
+        let (pool, client) = candidates_fixture().await;
+        let cases = [
+            ("protected=true", vec![13]),
+            ("protected=false&verified=false", vec![11, 12, 10]),
+            // None of the users has ever tweeted.
+            ("verified=true&inactive_days=3650", vec![14]),
+        ];
+        for (filter, expected) in cases {
+            let query = format!("sort=followers_count&{}", filter);
+            let (user_ids, total, next_cursor) = candidates(&pool, &client, &query).await;
+            assert_eq!(user_ids, expected, "{}", filter);
+            assert_eq!(total, expected.len(), "{}", filter);
+            assert_eq!(next_cursor, None);
+        }
+    }
+
+    #[actix::test]
+    async fn test_get_remove_candidates_invalid_cursor() {
+        let (pool, client) = candidates_fixture().await;
+        for cursor in ["abc", "20_x", "_11", "20-11"] {
+            let uri = format!("/accounts/1/remove_candidates?cursor={}", cursor);
+            let request = test::TestRequest::get().uri(&uri);
+            let (status, _) = respond(&pool, &client, false, request).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", cursor);
+        }
+    }
+
+    #[test]
+    fn test_diff_fields() {
+        let old = json!({"name": "alice", "location": null, "url": "https://example.com"});
+        let new = json!({"name": "Alice", "location": null, "verified": true});
+        let changes = diff_fields(&old, &new)
+            .into_iter()
+            .map(|change| (change.field, change.old, change.new))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                ("name".to_string(), json!("alice"), json!("Alice")),
+                ("url".to_string(), json!("https://example.com"), Value::Null),
+                ("verified".to_string(), Value::Null, json!(true)),
+            ]
+        );
+        assert!(diff_fields(&old, &old).is_empty());
+    }
+
+    #[actix::test]
+    async fn test_get_user_history() {
+        let pool = InMemoryPool::default();
+        let mut alice = user(10, "alice");
+        pool.put_user_info(&alice).await.unwrap();
+        // Tweeting alone does not make a new version.
+        alice.statuses_count = 5;
+        pool.put_user_info(&alice).await.unwrap();
+        alice.name = "Alice".to_string();
+        pool.put_user_info(&alice).await.unwrap();
+
+        let request = test::TestRequest::get().uri("/users/10/history");
+        let response = call(&pool, &fake_client(), false, request).await;
+        let diffs = response["diffs"].as_array().unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0]["changes"],
+            json!([{"field": "name", "old": "alice", "new": "Alice"}])
+        );
+    }
+
+    #[actix::test]
+    async fn test_remove_user() {
+        let pool = InMemoryPool::default();
+        let client = fake_client();
+        client.add_friend(10);
+        client.add_friend(11);
+
+        let request = |user_id: i64| {
+            test::TestRequest::post()
+                .uri("/accounts/1/remove_user")
+                .set_json(&json!({ "user_id": user_id }))
+        };
+        let response = call(&pool, &client, false, request(10)).await;
+        assert_eq!(response["screen_name"], "user10");
+        let response = call(&pool, &client, true, request(11)).await;
+        assert_eq!(response, Value::Null);
+
+        // Dry runs only record the unfollow.
+        assert_eq!(client.friends(), vec![11]);
+        let results = pool
+            .get_actions("1", &ActionFilter::default(), 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|action| (action.target_id, action.result))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            vec![(11, "dry_run".to_string()), (10, "succeeded".to_string())]
+        );
+        for user_id in [10, 11] {
+            assert!(pool.is_blocked_user("1", user_id).await.unwrap());
+        }
+    }
+
+    #[actix::test]
+    async fn test_remove_users() {
+        let pool = InMemoryPool::default();
+        let client = fake_client();
+        for id in 10..=12 {
+            client.add_friend(id);
+        }
+        // The automatic unfollow queued below would be dropped since the user follows back.
+        client.add_follower(12);
+        pool.put_allowed_user(
+            "1",
+            &AllowedUser {
+                user_id: 11,
+                reason: "friend".to_string(),
+                created_at: 0,
+            },
+        )
+        .await
+        .unwrap();
+        let queued = enqueue_action(
+            &pool,
+            "1",
+            ActionKind::Unfollow,
+            12,
+            Actor::InvalidUserRemover,
+            "spam",
+        )
         .await
-        .map_err(|e| anyhow::Error::from(e))?;
-    log::info!("Removed @{}", result.response.screen_name);
-    Ok(HttpResponse::Ok().json(result.response))
+        .unwrap();
+
+        let request = test::TestRequest::post()
+            .uri("/accounts/1/remove_users")
+            .set_json(&json!({ "user_ids": [10, 11, 12, 13] }));
+        let response = call(&pool, &client, false, request).await;
+        assert_eq!(response["progress"], json!({"pending": 2, "skipped": 2}));
+        let statuses = response["users"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|user| {
+                (
+                    user["user_id"].clone(),
+                    user["status"].clone(),
+                    user["error"].clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            vec![
+                (json!(10), json!("pending"), Value::Null),
+                (json!(11), json!("skipped"), json!("allowlisted")),
+                (json!(12), json!("pending"), Value::Null),
+                (json!(13), json!("skipped"), json!("not_following")),
+            ]
+        );
+
+        // The unfollow queued before is reused as a manual one.
+        let actions = pool
+            .get_queued_actions("1", &QueueFilter::default(), 10)
+            .await
+            .unwrap();
+        assert_eq!(actions.len(), 2);
+        let reused = actions
+            .iter()
+            .find(|action| action.id == queued.id)
+            .unwrap();
+        assert_eq!(reused.actor, "remove_user");
+        assert_eq!(reused.reason, "removed manually");
+        assert!(!pool.is_blocked_user("1", 11).await.unwrap());
+        assert!(pool.is_blocked_user("1", 12).await.unwrap());
+
+        let uri = format!("/accounts/1/remove_jobs/{}", response["id"]);
+        let job = call(&pool, &client, false, test::TestRequest::get().uri(&uri)).await;
+        assert_eq!(job, response);
+
+        let scheduler = ActionScheduler {
+            pool: pool.clone(),
+            client: client.clone(),
+            config: ActionSchedulerConfig::default(),
+            dry_run: false,
+        };
+        while scheduler.perform_next().await.unwrap() {}
+        let action = pool
+            .get_queued_actions_by_ids(&[queued.id])
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert_eq!(action.status, QueueStatus::Done);
+        assert_eq!(client.friends(), vec![11]);
+    }
+
+    #[actix::test]
+    async fn test_add_to_allowlist_cancels_queued_unfollow() {
+        let pool = InMemoryPool::default();
+        let client = fake_client();
+        let queued = enqueue_action(
+            &pool,
+            "1",
+            ActionKind::Unfollow,
+            10,
+            Actor::InvalidUserRemover,
+            "spam",
+        )
+        .await
+        .unwrap();
+
+        let request = test::TestRequest::post()
+            .uri("/accounts/1/allowlist")
+            .set_json(&json!({ "user_id": 10 }));
+        let response = call(&pool, &client, false, request).await;
+        assert_eq!(response["reason"], "added manually");
+        assert!(pool.is_allowed_user("1", 10).await.unwrap());
+        let action = pool
+            .get_queued_actions_by_ids(&[queued.id])
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert_eq!(action.status, QueueStatus::Cancelled);
+    }
+
+    #[actix::test]
+    async fn test_unknown_account() {
+        let pool = InMemoryPool::default();
+        let accounts = Accounts::<FakeTwitterClient>::new();
+        let app = test::init_service(
+            App::new()
+                .configure(config::<InMemoryPool, FakeTwitterClient>)
+                .data(accounts)
+                .data(pool),
+        )
+        .await;
+        let request = test::TestRequest::get().uri("/accounts/2/blocklist");
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
 }
@@ -0,0 +1,379 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueStatus {
+    Pending,
+    /// Claimed by the executor. Left over only if the process stopped while performing it.
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl QueueStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueueStatus::Pending => "pending",
+            QueueStatus::Running => "running",
+            QueueStatus::Done => "done",
+            QueueStatus::Failed => "failed",
+            QueueStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(status: &str) -> Result<Self> {
+        match status {
+            "pending" => Ok(QueueStatus::Pending),
+            "running" => Ok(QueueStatus::Running),
+            "done" => Ok(QueueStatus::Done),
+            "failed" => Ok(QueueStatus::Failed),
+            "cancelled" => Ok(QueueStatus::Cancelled),
+            _ => Err(anyhow!("Unknown queue status: {}", status)),
+        }
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        matches!(self, QueueStatus::Pending | QueueStatus::Running)
+    }
+}
+
+pub struct NewQueuedAction<'a> {
+    pub account: &'a str,
+    pub action: &'a str,
+    pub user_id: i64,
+    pub actor: &'a str,
+    pub reason: &'a str,
+    pub created_at: i64,
+}
+
+/// The open entry of an enqueued action.
+pub struct EnqueuedAction {
+    pub id: i64,
+    /// `false` if the same action on the user was already pending or running.
+    pub created: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct QueuedAction {
+    pub id: i64,
+    pub account: String,
+    pub action: String,
+    pub user_id: i64,
+    pub actor: String,
+    pub reason: String,
+    pub status: QueueStatus,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Deserialize, Default)]
+pub struct QueueFilter {
+    pub status: Option<QueueStatus>,
+    pub before_id: Option<i64>,
+}
+
+impl QueueFilter {
+    pub(crate) fn matches(&self, action: &QueuedAction) -> bool {
+        self.status.is_none_or(|status| status == action.status)
+            && self.before_id.is_none_or(|before_id| action.id < before_id)
+    }
+}
+
+/// Follows and unfollows decided by the workers, performed by `ActionScheduler`.
+#[async_trait]
+pub trait ActionQueueClient {
+    /// Returns the existing entry if the same action on the user is already pending or running.
+    async fn enqueue_action(&self, action: &NewQueuedAction<'_>) -> Result<EnqueuedAction>;
+    /// Marks the oldest pending action of the account among `actions` that is due at `now` as
+    /// running, counting the attempt, and returns it.
+    async fn claim_queued_action(
+        &self,
+        account: &str,
+        actions: &[&str],
+        now: i64,
+    ) -> Result<Option<QueuedAction>>;
+    /// Records the outcome of a claimed action. A `Pending` status schedules another attempt at
+    /// `next_attempt_at`.
+    async fn update_queued_action(
+        &self,
+        id: i64,
+        status: QueueStatus,
+        last_error: Option<&str>,
+        next_attempt_at: i64,
+        now: i64,
+    ) -> Result<()>;
+    /// Puts the account's running actions back to pending. Returns how many were reset.
+    async fn reset_running_actions(&self, account: &str, now: i64) -> Result<u64>;
+    /// Newest first.
+    async fn get_queued_actions(
+        &self,
+        account: &str,
+        filter: &QueueFilter,
+        limit: i64,
+    ) -> Result<Vec<QueuedAction>>;
+    /// Cancels a pending action. Returns `false` if there is no such action.
+    async fn cancel_queued_action(&self, account: &str, id: i64, now: i64) -> Result<bool>;
+    /// The pending or running entry of the action on the user, if any.
+    async fn get_open_queued_action(
+        &self,
+        account: &str,
+        action: &str,
+        user_id: i64,
+    ) -> Result<Option<QueuedAction>>;
+    async fn get_queued_actions_by_ids(&self, ids: &[i64]) -> Result<Vec<QueuedAction>>;
+    /// Hands a pending or running entry over to `actor`. Returns `false` if the entry is closed.
+    async fn reassign_queued_action(
+        &self,
+        id: i64,
+        actor: &str,
+        reason: &str,
+        now: i64,
+    ) -> Result<bool>;
+    /// Users whose `action` entry of the account was cancelled or failed at or after `since`.
+    async fn get_dropped_user_ids(
+        &self,
+        account: &str,
+        action: &str,
+        since: i64,
+    ) -> Result<Vec<i64>>;
+}
+
+fn queued_action(row: PgRow) -> Result<QueuedAction, sqlx::Error> {
+    let status: String = row.try_get("status")?;
+    Ok(QueuedAction {
+        id: row.try_get("id")?,
+        account: row.try_get("account")?,
+        action: row.try_get("action")?,
+        user_id: row.try_get("user_id")?,
+        actor: row.try_get("actor")?,
+        reason: row.try_get("reason")?,
+        status: QueueStatus::parse(&status).map_err(|e| sqlx::Error::Decode(e.into()))?,
+        attempts: row.try_get("attempts")?,
+        last_error: row.try_get("last_error")?,
+        next_attempt_at: row.try_get("next_attempt_at")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+#[async_trait]
+impl ActionQueueClient for PgPool {
+    async fn enqueue_action(&self, action: &NewQueuedAction<'_>) -> Result<EnqueuedAction> {
+        // The no-op update locks the open entry on conflict so that its id is returned as well.
+        let (id, created) = sqlx::query_as(
+            r"
+            INSERT INTO action_queue (account, action, user_id, actor, reason, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            ON CONFLICT (account, action, user_id) WHERE status IN ('pending', 'running')
+            DO UPDATE SET updated_at = action_queue.updated_at
+            RETURNING id, xmax = 0
+        ",
+        )
+        .bind(action.account)
+        .bind(action.action)
+        .bind(action.user_id)
+        .bind(action.actor)
+        .bind(action.reason)
+        .bind(action.created_at)
+        .fetch_one(self)
+        .await?;
+        Ok(EnqueuedAction { id, created })
+    }
+
+    async fn claim_queued_action(
+        &self,
+        account: &str,
+        actions: &[&str],
+        now: i64,
+    ) -> Result<Option<QueuedAction>> {
+        let action = sqlx::query(
+            r"
+            UPDATE action_queue
+            SET status = 'running', attempts = attempts + 1, updated_at = $3
+            WHERE id = (
+                SELECT id FROM action_queue
+                WHERE account = $1 AND action = ANY($2)
+                AND status = 'pending' AND next_attempt_at <= $3
+                ORDER BY id
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *
+        ",
+        )
+        .bind(account)
+        .bind(actions)
+        .bind(now)
+        .try_map(queued_action)
+        .fetch_optional(self)
+        .await?;
+        Ok(action)
+    }
+
+    async fn update_queued_action(
+        &self,
+        id: i64,
+        status: QueueStatus,
+        last_error: Option<&str>,
+        next_attempt_at: i64,
+        now: i64,
+    ) -> Result<()> {
+        sqlx::query(
+            r"
+            UPDATE action_queue
+            SET status = $2, last_error = $3, next_attempt_at = $4, updated_at = $5
+            WHERE id = $1
+        ",
+        )
+        .bind(id)
+        .bind(status.as_str())
+        .bind(last_error)
+        .bind(next_attempt_at)
+        .bind(now)
+        .execute(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn reset_running_actions(&self, account: &str, now: i64) -> Result<u64> {
+        let result = sqlx::query(
+            r"
+            UPDATE action_queue SET status = 'pending', updated_at = $2
+            WHERE account = $1 AND status = 'running'
+        ",
+        )
+        .bind(account)
+        .bind(now)
+        .execute(self)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn get_queued_actions(
+        &self,
+        account: &str,
+        filter: &QueueFilter,
+        limit: i64,
+    ) -> Result<Vec<QueuedAction>> {
+        let actions = sqlx::query(
+            r"
+            SELECT * FROM action_queue
+            WHERE account = $1
+            AND ($2::TEXT IS NULL OR status = $2)
+            AND ($3::BIGINT IS NULL OR id < $3)
+            ORDER BY id DESC
+            LIMIT $4
+        ",
+        )
+        .bind(account)
+        .bind(filter.status.map(|status| status.as_str()))
+        .bind(filter.before_id)
+        .bind(limit)
+        .try_map(queued_action)
+        .fetch_all(self)
+        .await?;
+        Ok(actions)
+    }
+
+    async fn cancel_queued_action(&self, account: &str, id: i64, now: i64) -> Result<bool> {
+        let result = sqlx::query(
+            r"
+            UPDATE action_queue SET status = 'cancelled', updated_at = $3
+            WHERE account = $1 AND id = $2 AND status = 'pending'
+        ",
+        )
+        .bind(account)
+        .bind(id)
+        .bind(now)
+        .execute(self)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_open_queued_action(
+        &self,
+        account: &str,
+        action: &str,
+        user_id: i64,
+    ) -> Result<Option<QueuedAction>> {
+        let action = sqlx::query(
+            r"
+            SELECT * FROM action_queue
+            WHERE account = $1 AND action = $2 AND user_id = $3
+            AND status IN ('pending', 'running')
+        ",
+        )
+        .bind(account)
+        .bind(action)
+        .bind(user_id)
+        .try_map(queued_action)
+        .fetch_optional(self)
+        .await?;
+        Ok(action)
+    }
+
+    async fn get_queued_actions_by_ids(&self, ids: &[i64]) -> Result<Vec<QueuedAction>> {
+        let actions = sqlx::query(
+            r"
+            SELECT * FROM action_queue WHERE id = ANY($1) ORDER BY id
+        ",
+        )
+        .bind(ids)
+        .try_map(queued_action)
+        .fetch_all(self)
+        .await?;
+        Ok(actions)
+    }
+
+    async fn reassign_queued_action(
+        &self,
+        id: i64,
+        actor: &str,
+        reason: &str,
+        now: i64,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r"
+            UPDATE action_queue SET actor = $2, reason = $3, updated_at = $4
+            WHERE id = $1 AND status IN ('pending', 'running')
+        ",
+        )
+        .bind(id)
+        .bind(actor)
+        .bind(reason)
+        .bind(now)
+        .execute(self)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_dropped_user_ids(
+        &self,
+        account: &str,
+        action: &str,
+        since: i64,
+    ) -> Result<Vec<i64>> {
+        let user_ids = sqlx::query(
+            r"
+            SELECT DISTINCT user_id FROM action_queue
+            WHERE account = $1 AND action = $2 AND status IN ('cancelled', 'failed')
+            AND updated_at >= $3
+        ",
+        )
+        .bind(account)
+        .bind(action)
+        .bind(since)
+        .try_map(|row: PgRow| row.try_get::<i64, _>("user_id"))
+        .fetch_all(self)
+        .await?;
+        Ok(user_ids)
+    }
+}
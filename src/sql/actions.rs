@@ -0,0 +1,174 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+pub struct NewAction<'a> {
+    pub account: &'a str,
+    pub actor: &'a str,
+    pub target_id: i64,
+    pub action: &'a str,
+    pub reason: &'a str,
+    pub result: &'a str,
+    pub error: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ActionEntry {
+    pub id: i64,
+    pub account: String,
+    pub actor: String,
+    pub target_id: i64,
+    pub action: String,
+    pub reason: String,
+    pub result: String,
+    pub error: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Deserialize, Default)]
+pub struct ActionFilter {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target_id: Option<i64>,
+    pub result: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub before_id: Option<i64>,
+}
+
+impl ActionFilter {
+    pub(crate) fn matches(&self, entry: &ActionEntry) -> bool {
+        self.actor
+            .as_ref()
+            .is_none_or(|actor| actor == &entry.actor)
+            && self
+                .action
+                .as_ref()
+                .is_none_or(|action| action == &entry.action)
+            && self.target_id.is_none_or(|id| id == entry.target_id)
+            && self
+                .result
+                .as_ref()
+                .is_none_or(|result| result == &entry.result)
+            && self.since.is_none_or(|since| since <= entry.created_at)
+            && self.until.is_none_or(|until| entry.created_at < until)
+            && self.before_id.is_none_or(|before_id| entry.id < before_id)
+    }
+}
+
+#[async_trait]
+pub trait ActionLogClient {
+    async fn put_action(&self, action: &NewAction<'_>) -> Result<()>;
+    async fn get_actions(
+        &self,
+        account: &str,
+        filter: &ActionFilter,
+        limit: i64,
+    ) -> Result<Vec<ActionEntry>>;
+    /// Number of `action`s of the account since `since` that ended with one of `results`.
+    async fn count_actions(
+        &self,
+        account: &str,
+        action: &str,
+        results: &[&str],
+        since: i64,
+    ) -> Result<i64>;
+}
+
+#[async_trait]
+impl ActionLogClient for PgPool {
+    async fn put_action(&self, action: &NewAction<'_>) -> Result<()> {
+        sqlx::query(
+            r"
+            INSERT INTO actions (account, actor, target_id, action, reason, result, error, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ",
+        )
+        .bind(action.account)
+        .bind(action.actor)
+        .bind(action.target_id)
+        .bind(action.action)
+        .bind(action.reason)
+        .bind(action.result)
+        .bind(action.error.as_deref())
+        .bind(action.created_at)
+        .execute(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_actions(
+        &self,
+        account: &str,
+        filter: &ActionFilter,
+        limit: i64,
+    ) -> Result<Vec<ActionEntry>> {
+        let actions = sqlx::query(
+            r"
+            SELECT id, account, actor, target_id, action, reason, result, error, created_at
+            FROM actions
+            WHERE account = $1
+            AND ($2::TEXT IS NULL OR actor = $2)
+            AND ($3::TEXT IS NULL OR action = $3)
+            AND ($4::BIGINT IS NULL OR target_id = $4)
+            AND ($5::TEXT IS NULL OR result = $5)
+            AND ($6::BIGINT IS NULL OR created_at >= $6)
+            AND ($7::BIGINT IS NULL OR created_at < $7)
+            AND ($8::BIGINT IS NULL OR id < $8)
+            ORDER BY id DESC
+            LIMIT $9
+        ",
+        )
+        .bind(account)
+        .bind(filter.actor.as_deref())
+        .bind(filter.action.as_deref())
+        .bind(filter.target_id)
+        .bind(filter.result.as_deref())
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(filter.before_id)
+        .bind(limit)
+        .try_map(|row: PgRow| {
+            Ok(ActionEntry {
+                id: row.try_get("id")?,
+                account: row.try_get("account")?,
+                actor: row.try_get("actor")?,
+                target_id: row.try_get("target_id")?,
+                action: row.try_get("action")?,
+                reason: row.try_get("reason")?,
+                result: row.try_get("result")?,
+                error: row.try_get("error")?,
+                created_at: row.try_get("created_at")?,
+            })
+        })
+        .fetch_all(self)
+        .await?;
+        Ok(actions)
+    }
+
+    async fn count_actions(
+        &self,
+        account: &str,
+        action: &str,
+        results: &[&str],
+        since: i64,
+    ) -> Result<i64> {
+        let count = sqlx::query(
+            r"
+            SELECT COUNT(*) AS count FROM actions
+            WHERE account = $1 AND action = $2 AND created_at >= $3 AND result = ANY($4)
+        ",
+        )
+        .bind(account)
+        .bind(action)
+        .bind(since)
+        .bind(results)
+        .try_map(|row: PgRow| row.try_get::<i64, _>("count"))
+        .fetch_one(self)
+        .await?;
+        Ok(count)
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+#[derive(Serialize, Clone)]
+pub struct AllowedUser {
+    pub user_id: i64,
+    pub reason: String,
+    pub created_at: i64,
+}
+
+/// Users that are kept followed even though they do not follow back. Automated unfollows skip
+/// them; removing one by hand is still possible.
+#[async_trait]
+pub trait AllowlistClient {
+    async fn put_allowed_user(&self, account: &str, user: &AllowedUser) -> Result<()>;
+    async fn delete_allowed_user(&self, account: &str, user_id: i64) -> Result<bool>;
+    async fn get_allowed_users(&self, account: &str) -> Result<Vec<AllowedUser>>;
+    async fn is_allowed_user(&self, account: &str, user_id: i64) -> Result<bool>;
+}
+
+#[async_trait]
+impl AllowlistClient for PgPool {
+    async fn put_allowed_user(&self, account: &str, user: &AllowedUser) -> Result<()> {
+        sqlx::query(
+            r"
+            INSERT INTO unfollow_allowlist (account, user_id, reason, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (account, user_id)
+            DO UPDATE SET reason = EXCLUDED.reason, created_at = EXCLUDED.created_at
+        ",
+        )
+        .bind(account)
+        .bind(user.user_id)
+        .bind(&user.reason)
+        .bind(user.created_at)
+        .execute(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_allowed_user(&self, account: &str, user_id: i64) -> Result<bool> {
+        let result = sqlx::query(
+            r"
+            DELETE FROM unfollow_allowlist WHERE account = $1 AND user_id = $2
+        ",
+        )
+        .bind(account)
+        .bind(user_id)
+        .execute(self)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_allowed_users(&self, account: &str) -> Result<Vec<AllowedUser>> {
+        let users = sqlx::query(
+            r"
+            SELECT user_id, reason, created_at FROM unfollow_allowlist
+            WHERE account = $1
+            ORDER BY created_at DESC
+        ",
+        )
+        .bind(account)
+        .try_map(|row: PgRow| {
+            Ok(AllowedUser {
+                user_id: row.try_get("user_id")?,
+                reason: row.try_get("reason")?,
+                created_at: row.try_get("created_at")?,
+            })
+        })
+        .fetch_all(self)
+        .await?;
+        Ok(users)
+    }
+
+    async fn is_allowed_user(&self, account: &str, user_id: i64) -> Result<bool> {
+        let allowed = sqlx::query(
+            r"
+            SELECT 1 FROM unfollow_allowlist WHERE account = $1 AND user_id = $2
+        ",
+        )
+        .bind(account)
+        .bind(user_id)
+        .fetch_optional(self)
+        .await?;
+        Ok(allowed.is_some())
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+#[derive(Serialize, Clone)]
+pub struct BlockedUser {
+    pub user_id: i64,
+    pub reason: String,
+    pub created_at: i64,
+}
+
+#[async_trait]
+pub trait BlocklistClient {
+    async fn put_blocked_user(&self, account: &str, user: &BlockedUser) -> Result<()>;
+    async fn delete_blocked_user(&self, account: &str, user_id: i64) -> Result<bool>;
+    async fn get_blocked_users(&self, account: &str) -> Result<Vec<BlockedUser>>;
+    async fn is_blocked_user(&self, account: &str, user_id: i64) -> Result<bool>;
+}
+
+#[async_trait]
+impl BlocklistClient for PgPool {
+    async fn put_blocked_user(&self, account: &str, user: &BlockedUser) -> Result<()> {
+        sqlx::query(
+            r"
+            INSERT INTO follow_blocklist (account, user_id, reason, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (account, user_id)
+            DO UPDATE SET reason = EXCLUDED.reason, created_at = EXCLUDED.created_at
+        ",
+        )
+        .bind(account)
+        .bind(user.user_id)
+        .bind(&user.reason)
+        .bind(user.created_at)
+        .execute(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_blocked_user(&self, account: &str, user_id: i64) -> Result<bool> {
+        let result = sqlx::query(
+            r"
+            DELETE FROM follow_blocklist WHERE account = $1 AND user_id = $2
+        ",
+        )
+        .bind(account)
+        .bind(user_id)
+        .execute(self)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_blocked_users(&self, account: &str) -> Result<Vec<BlockedUser>> {
+        let users = sqlx::query(
+            r"
+            SELECT user_id, reason, created_at FROM follow_blocklist
+            WHERE account = $1
+            ORDER BY created_at DESC
+        ",
+        )
+        .bind(account)
+        .try_map(|row: PgRow| {
+            Ok(BlockedUser {
+                user_id: row.try_get("user_id")?,
+                reason: row.try_get("reason")?,
+                created_at: row.try_get("created_at")?,
+            })
+        })
+        .fetch_all(self)
+        .await?;
+        Ok(users)
+    }
+
+    async fn is_blocked_user(&self, account: &str, user_id: i64) -> Result<bool> {
+        let blocked = sqlx::query(
+            r"
+            SELECT 1 FROM follow_blocklist WHERE account = $1 AND user_id = $2
+        ",
+        )
+        .bind(account)
+        .bind(user_id)
+        .fetch_optional(self)
+        .await?;
+        Ok(blocked.is_some())
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+/// A remove candidate hidden until `until`.
+#[derive(Serialize, Clone)]
+pub struct DeferredUser {
+    pub user_id: i64,
+    pub until: i64,
+    pub created_at: i64,
+}
+
+#[async_trait]
+pub trait DeferredUserClient {
+    /// Replaces any earlier deferral of the user.
+    async fn put_deferred_user(&self, account: &str, user: &DeferredUser) -> Result<()>;
+    /// Users whose deferral has not expired at `now`.
+    async fn get_deferred_user_ids(&self, account: &str, now: i64) -> Result<Vec<i64>>;
+}
+
+#[async_trait]
+impl DeferredUserClient for PgPool {
+    async fn put_deferred_user(&self, account: &str, user: &DeferredUser) -> Result<()> {
+        sqlx::query(
+            r"
+            INSERT INTO deferred_users (account, user_id, until, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (account, user_id)
+            DO UPDATE SET until = EXCLUDED.until, created_at = EXCLUDED.created_at
+        ",
+        )
+        .bind(account)
+        .bind(user.user_id)
+        .bind(user.until)
+        .bind(user.created_at)
+        .execute(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_deferred_user_ids(&self, account: &str, now: i64) -> Result<Vec<i64>> {
+        let user_ids = sqlx::query(
+            r"
+            SELECT user_id FROM deferred_users WHERE account = $1 AND until > $2
+        ",
+        )
+        .bind(account)
+        .bind(now)
+        .try_map(|row: PgRow| row.try_get::<i64, _>("user_id"))
+        .fetch_all(self)
+        .await?;
+        Ok(user_ids)
+    }
+}
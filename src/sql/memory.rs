@@ -0,0 +1,961 @@
+use crate::current_time_duration;
+use crate::sql::user_history::profile_json;
+use crate::sql::{
+    relationship_events, ActionEntry, ActionFilter, ActionLogClient, ActionQueueClient,
+    AllowedUser, AllowlistClient, BlockedUser, BlocklistClient, DeferredUser, DeferredUserClient,
+    EnqueuedAction, NewAction, NewQueuedAction, PgPoolExt, QueueFilter, QueueStatus, QueuedAction,
+    RelationshipEvent, RelationshipEventClient, RelationshipEventFilter, RemoveJob,
+    RemoveJobClient, RemoveJobUser, SkippedUser, SkippedUserClient, Snapshot, SnapshotClient,
+    UserHistoryClient, UserHistoryEntry, UserIdClient, UserIdEntry, UserStatus, UserStatusClient,
+    WebhookCursorClient,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use egg_mode::user::TwitterUser;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// In-memory implementation of the storage traits, for tests and running without Postgres.
+#[derive(Clone, Default)]
+pub struct InMemoryPool {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    friends_ids: BTreeMap<String, BTreeMap<i64, IdRow>>,
+    followers_ids: BTreeMap<String, BTreeMap<i64, IdRow>>,
+    user_data: BTreeMap<i64, UserDataRow>,
+    actions: Vec<ActionEntry>,
+    blocklist: BTreeMap<(String, i64), BlockedUser>,
+    allowlist: BTreeMap<(String, i64), AllowedUser>,
+    deferred_users: BTreeMap<(String, i64), DeferredUser>,
+    follow_back_skips: BTreeMap<(String, i64), SkippedUser>,
+    relationship_events: Vec<RelationshipEvent>,
+    snapshots: BTreeMap<i64, Snapshot>,
+    snapshot_members: BTreeMap<i64, BTreeSet<i64>>,
+    next_snapshot_id: i64,
+    webhook_cursors: BTreeMap<(String, String), i64>,
+    user_data_history: Vec<UserHistoryEntry>,
+    action_queue: BTreeMap<i64, QueuedAction>,
+    next_queued_action_id: i64,
+    remove_jobs: BTreeMap<i64, RemoveJob>,
+    next_remove_job_id: i64,
+}
+
+struct UserDataRow {
+    data: Option<TwitterUser>,
+    fetched_at: i64,
+    status: UserStatus,
+}
+
+struct IdRow {
+    confirmed_at: i64,
+    created_at: i64,
+}
+
+impl State {
+    fn ids(&self, account: &str, follower: bool) -> impl Iterator<Item = (&i64, &IdRow)> {
+        let tables = if follower {
+            &self.followers_ids
+        } else {
+            &self.friends_ids
+        };
+        tables.get(account).into_iter().flatten()
+    }
+    fn ids_mut(&mut self, account: &str, follower: bool) -> &mut BTreeMap<i64, IdRow> {
+        let tables = if follower {
+            &mut self.followers_ids
+        } else {
+            &mut self.friends_ids
+        };
+        tables.entry(account.to_string()).or_default()
+    }
+}
+
+#[async_trait]
+impl PgPoolExt for InMemoryPool {
+    async fn put_user_ids(&self, account: &str, ids: &[u64], follower: bool) -> Result<()> {
+        let unixtime_second = current_time_duration().as_secs() as i64;
+        let mut state = self.state.lock().unwrap();
+        let table = state.ids_mut(account, follower);
+        for &id in ids {
+            table
+                .entry(id as i64)
+                .and_modify(|row| row.confirmed_at = unixtime_second)
+                .or_insert(IdRow {
+                    confirmed_at: unixtime_second,
+                    created_at: unixtime_second,
+                });
+        }
+        Ok(())
+    }
+    async fn get_user_ids(
+        &self,
+        account: &str,
+        follower: bool,
+        confirmed_after: i64,
+    ) -> Result<Vec<i64>> {
+        let state = self.state.lock().unwrap();
+        let ids = state
+            .ids(account, follower)
+            .filter(|(_, row)| row.confirmed_at > confirmed_after)
+            .map(|(&id, _)| id)
+            .collect();
+        Ok(ids)
+    }
+
+    async fn get_user_info(&self, id: i64) -> Result<Option<TwitterUser>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.user_data.get(&id).and_then(|row| row.data.clone()))
+    }
+    async fn get_user_infos(&self, ids: &[i64]) -> Result<HashMap<i64, TwitterUser>> {
+        let state = self.state.lock().unwrap();
+        let users = ids
+            .iter()
+            .filter_map(|id| {
+                let user = state.user_data.get(id)?.data.clone()?;
+                Some((*id, user))
+            })
+            .collect();
+        Ok(users)
+    }
+    async fn put_user_info(&self, user: &TwitterUser) -> Result<()> {
+        let user_id = user.id as i64;
+        let fetched_at = current_time_duration().as_secs() as i64;
+        let profile = profile_json(user)?;
+        let mut state = self.state.lock().unwrap();
+        state.user_data.insert(
+            user_id,
+            UserDataRow {
+                data: Some(user.clone()),
+                fetched_at,
+                status: UserStatus::Active,
+            },
+        );
+        let changed = state
+            .user_data_history
+            .iter()
+            .rev()
+            .find(|entry| entry.user_id == user_id)
+            .is_none_or(|latest| latest.data != profile);
+        if changed {
+            let id = state.user_data_history.len() as i64 + 1;
+            state.user_data_history.push(UserHistoryEntry {
+                id,
+                user_id,
+                data: profile,
+                fetched_at,
+            });
+        }
+        Ok(())
+    }
+
+    async fn get_no_data_user_ids(
+        &self,
+        account: &str,
+        confirmed_after: i64,
+        size: i64,
+    ) -> Result<Vec<i64>> {
+        let state = self.state.lock().unwrap();
+        let mut ids = vec![];
+        for &follower in &[false, true] {
+            let no_data_ids = state
+                .ids(account, follower)
+                .filter(|(id, row)| {
+                    row.confirmed_at > confirmed_after && !state.user_data.contains_key(id)
+                })
+                .map(|(&id, _)| id)
+                .take(size as usize);
+            ids.extend(no_data_ids);
+        }
+        Ok(ids)
+    }
+
+    async fn get_stale_user_ids(
+        &self,
+        account: &str,
+        confirmed_after: i64,
+        fetched_before: i64,
+        size: i64,
+    ) -> Result<Vec<i64>> {
+        let state = self.state.lock().unwrap();
+        let related = |id: &i64| {
+            [&state.friends_ids, &state.followers_ids]
+                .iter()
+                .any(|tables| {
+                    tables
+                        .get(account)
+                        .and_then(|table| table.get(id))
+                        .is_some_and(|row| row.confirmed_at > confirmed_after)
+                })
+        };
+        let mut rows = state
+            .user_data
+            .iter()
+            .filter(|(id, row)| {
+                row.status == UserStatus::Active && row.fetched_at < fetched_before && related(id)
+            })
+            .map(|(&id, row)| (row.fetched_at, id))
+            .collect::<Vec<_>>();
+        rows.sort();
+        Ok(rows
+            .into_iter()
+            .take(size as usize)
+            .map(|(_, id)| id)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl UserIdClient for InMemoryPool {
+    async fn get_all_user_id_entries(
+        &self,
+        account: &str,
+        follower: bool,
+    ) -> Result<Vec<UserIdEntry>> {
+        let state = self.state.lock().unwrap();
+        let entries = state
+            .ids(account, follower)
+            .map(|(&id, row)| UserIdEntry {
+                id,
+                confirmed_at: row.confirmed_at,
+                created_at: row.created_at,
+            })
+            .collect();
+        Ok(entries)
+    }
+}
+
+#[async_trait]
+impl ActionLogClient for InMemoryPool {
+    async fn put_action(&self, action: &NewAction<'_>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.actions.len() as i64 + 1;
+        state.actions.push(ActionEntry {
+            id,
+            account: action.account.to_string(),
+            actor: action.actor.to_string(),
+            target_id: action.target_id,
+            action: action.action.to_string(),
+            reason: action.reason.to_string(),
+            result: action.result.to_string(),
+            error: action.error.clone(),
+            created_at: action.created_at,
+        });
+        Ok(())
+    }
+
+    async fn get_actions(
+        &self,
+        account: &str,
+        filter: &ActionFilter,
+        limit: i64,
+    ) -> Result<Vec<ActionEntry>> {
+        let state = self.state.lock().unwrap();
+        let actions = state
+            .actions
+            .iter()
+            .rev()
+            .filter(|entry| entry.account == account && filter.matches(entry))
+            .take(limit as usize)
+            .cloned()
+            .collect();
+        Ok(actions)
+    }
+
+    async fn count_actions(
+        &self,
+        account: &str,
+        action: &str,
+        results: &[&str],
+        since: i64,
+    ) -> Result<i64> {
+        let state = self.state.lock().unwrap();
+        let count = state
+            .actions
+            .iter()
+            .filter(|entry| {
+                entry.account == account
+                    && entry.action == action
+                    && entry.created_at >= since
+                    && results.contains(&entry.result.as_str())
+            })
+            .count();
+        Ok(count as i64)
+    }
+}
+
+#[async_trait]
+impl BlocklistClient for InMemoryPool {
+    async fn put_blocked_user(&self, account: &str, user: &BlockedUser) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .blocklist
+            .insert((account.to_string(), user.user_id), user.clone());
+        Ok(())
+    }
+
+    async fn delete_blocked_user(&self, account: &str, user_id: i64) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        let removed = state.blocklist.remove(&(account.to_string(), user_id));
+        Ok(removed.is_some())
+    }
+
+    async fn get_blocked_users(&self, account: &str) -> Result<Vec<BlockedUser>> {
+        let state = self.state.lock().unwrap();
+        let mut users = state
+            .blocklist
+            .iter()
+            .filter(|((blocked_account, _), _)| blocked_account == account)
+            .map(|(_, user)| user.clone())
+            .collect::<Vec<_>>();
+        users.sort_by_key(|user| -user.created_at);
+        Ok(users)
+    }
+
+    async fn is_blocked_user(&self, account: &str, user_id: i64) -> Result<bool> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .blocklist
+            .contains_key(&(account.to_string(), user_id)))
+    }
+}
+
+#[async_trait]
+impl AllowlistClient for InMemoryPool {
+    async fn put_allowed_user(&self, account: &str, user: &AllowedUser) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .allowlist
+            .insert((account.to_string(), user.user_id), user.clone());
+        Ok(())
+    }
+
+    async fn delete_allowed_user(&self, account: &str, user_id: i64) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        let removed = state.allowlist.remove(&(account.to_string(), user_id));
+        Ok(removed.is_some())
+    }
+
+    async fn get_allowed_users(&self, account: &str) -> Result<Vec<AllowedUser>> {
+        let state = self.state.lock().unwrap();
+        let mut users = state
+            .allowlist
+            .iter()
+            .filter(|((allowed_account, _), _)| allowed_account == account)
+            .map(|(_, user)| user.clone())
+            .collect::<Vec<_>>();
+        users.sort_by_key(|user| -user.created_at);
+        Ok(users)
+    }
+
+    async fn is_allowed_user(&self, account: &str, user_id: i64) -> Result<bool> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .allowlist
+            .contains_key(&(account.to_string(), user_id)))
+    }
+}
+
+#[async_trait]
+impl DeferredUserClient for InMemoryPool {
+    async fn put_deferred_user(&self, account: &str, user: &DeferredUser) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .deferred_users
+            .insert((account.to_string(), user.user_id), user.clone());
+        Ok(())
+    }
+
+    async fn get_deferred_user_ids(&self, account: &str, now: i64) -> Result<Vec<i64>> {
+        let state = self.state.lock().unwrap();
+        let user_ids = state
+            .deferred_users
+            .iter()
+            .filter(|((deferred_account, _), user)| deferred_account == account && user.until > now)
+            .map(|(_, user)| user.user_id)
+            .collect();
+        Ok(user_ids)
+    }
+}
+
+#[async_trait]
+impl SkippedUserClient for InMemoryPool {
+    async fn put_skipped_user(&self, account: &str, user: &SkippedUser) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .follow_back_skips
+            .insert((account.to_string(), user.user_id), user.clone());
+        Ok(())
+    }
+
+    async fn get_skipped_users(&self, account: &str) -> Result<Vec<SkippedUser>> {
+        let state = self.state.lock().unwrap();
+        let mut users = state
+            .follow_back_skips
+            .iter()
+            .filter(|((skipped_account, _), _)| skipped_account == account)
+            .map(|(_, user)| user.clone())
+            .collect::<Vec<_>>();
+        users.sort_by_key(|user| -user.skipped_at);
+        Ok(users)
+    }
+}
+
+#[async_trait]
+impl RelationshipEventClient for InMemoryPool {
+    async fn get_active_relationships(&self, account: &str, follower: bool) -> Result<Vec<i64>> {
+        let (started, ended) = relationship_events(follower);
+        let state = self.state.lock().unwrap();
+        let mut latest = BTreeMap::new();
+        for entry in state.relationship_events.iter() {
+            if entry.account == account && (entry.event == started || entry.event == ended) {
+                latest.insert(entry.user_id, entry.event.as_str());
+            }
+        }
+        let user_ids = latest
+            .into_iter()
+            .filter(|(_, event)| *event == started)
+            .map(|(user_id, _)| user_id)
+            .collect();
+        Ok(user_ids)
+    }
+
+    async fn get_relationship_started_at(
+        &self,
+        account: &str,
+        follower: bool,
+        user_ids: &[i64],
+    ) -> Result<HashMap<i64, i64>> {
+        let (started, _) = relationship_events(follower);
+        let state = self.state.lock().unwrap();
+        let mut started_at = HashMap::new();
+        for entry in state.relationship_events.iter() {
+            if entry.account == account
+                && entry.event == started
+                && user_ids.contains(&entry.user_id)
+            {
+                started_at.insert(entry.user_id, entry.created_at);
+            }
+        }
+        Ok(started_at)
+    }
+
+    async fn get_relationship_events(
+        &self,
+        account: &str,
+        filter: &RelationshipEventFilter,
+        limit: i64,
+    ) -> Result<Vec<RelationshipEvent>> {
+        let state = self.state.lock().unwrap();
+        let events = state
+            .relationship_events
+            .iter()
+            .rev()
+            .filter(|entry| entry.account == account && filter.matches(entry))
+            .take(limit as usize)
+            .cloned()
+            .collect();
+        Ok(events)
+    }
+
+    async fn get_relationship_events_after(
+        &self,
+        account: &str,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<RelationshipEvent>> {
+        let state = self.state.lock().unwrap();
+        let events = state
+            .relationship_events
+            .iter()
+            .filter(|entry| entry.account == account && entry.id > after_id)
+            .take(limit as usize)
+            .cloned()
+            .collect();
+        Ok(events)
+    }
+}
+
+#[async_trait]
+impl SnapshotClient for InMemoryPool {
+    async fn start_snapshot(&self, account: &str, follower: bool, started_at: i64) -> Result<i64> {
+        let mut state = self.state.lock().unwrap();
+        state.next_snapshot_id += 1;
+        let id = state.next_snapshot_id;
+        state.snapshots.insert(
+            id,
+            Snapshot {
+                id,
+                account: account.to_string(),
+                follower,
+                started_at,
+                finished_at: None,
+            },
+        );
+        Ok(id)
+    }
+
+    async fn put_snapshot_members(&self, snapshot_id: i64, user_ids: &[i64]) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .snapshot_members
+            .entry(snapshot_id)
+            .or_default()
+            .extend(user_ids.iter().copied());
+        Ok(())
+    }
+
+    async fn finish_snapshot(
+        &self,
+        snapshot_id: i64,
+        account: &str,
+        events: &[(&str, &[i64])],
+        finished_at: i64,
+    ) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        for (event, user_ids) in events {
+            for &user_id in user_ids.iter() {
+                let id = state.relationship_events.len() as i64 + 1;
+                state.relationship_events.push(RelationshipEvent {
+                    id,
+                    account: account.to_string(),
+                    user_id,
+                    event: event.to_string(),
+                    created_at: finished_at,
+                });
+            }
+        }
+        if let Some(snapshot) = state.snapshots.get_mut(&snapshot_id) {
+            snapshot.finished_at = Some(finished_at);
+        }
+        Ok(())
+    }
+
+    async fn get_complete_snapshot(
+        &self,
+        account: &str,
+        follower: bool,
+        before_id: Option<i64>,
+    ) -> Result<Option<Snapshot>> {
+        let state = self.state.lock().unwrap();
+        let snapshot = state
+            .snapshots
+            .values()
+            .rev()
+            .find(|snapshot| {
+                snapshot.account == account
+                    && snapshot.follower == follower
+                    && snapshot.finished_at.is_some()
+                    && before_id.is_none_or(|before_id| snapshot.id < before_id)
+            })
+            .cloned();
+        Ok(snapshot)
+    }
+
+    async fn get_snapshot_members(&self, snapshot_id: i64) -> Result<Vec<i64>> {
+        let state = self.state.lock().unwrap();
+        let user_ids = state
+            .snapshot_members
+            .get(&snapshot_id)
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect();
+        Ok(user_ids)
+    }
+
+    async fn get_snapshot_difference(
+        &self,
+        snapshot_id: i64,
+        excluded_snapshot_id: i64,
+    ) -> Result<Vec<i64>> {
+        let state = self.state.lock().unwrap();
+        let empty = BTreeSet::new();
+        let excluded = state
+            .snapshot_members
+            .get(&excluded_snapshot_id)
+            .unwrap_or(&empty);
+        let user_ids = state
+            .snapshot_members
+            .get(&snapshot_id)
+            .into_iter()
+            .flatten()
+            .filter(|user_id| !excluded.contains(user_id))
+            .copied()
+            .collect();
+        Ok(user_ids)
+    }
+
+    async fn delete_snapshots(&self, account: &str, follower: bool, before_id: i64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let ids = state
+            .snapshots
+            .values()
+            .filter(|snapshot| {
+                snapshot.account == account
+                    && snapshot.follower == follower
+                    && snapshot.id < before_id
+            })
+            .map(|snapshot| snapshot.id)
+            .collect::<Vec<_>>();
+        for id in ids {
+            state.snapshots.remove(&id);
+            state.snapshot_members.remove(&id);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WebhookCursorClient for InMemoryPool {
+    async fn get_webhook_cursor(&self, account: &str, url: &str) -> Result<Option<i64>> {
+        let state = self.state.lock().unwrap();
+        let key = (account.to_string(), url.to_string());
+        Ok(state.webhook_cursors.get(&key).copied())
+    }
+
+    async fn put_webhook_cursor(&self, account: &str, url: &str, last_event_id: i64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let key = (account.to_string(), url.to_string());
+        state.webhook_cursors.insert(key, last_event_id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserHistoryClient for InMemoryPool {
+    async fn get_user_history(&self, user_id: i64) -> Result<Vec<UserHistoryEntry>> {
+        let state = self.state.lock().unwrap();
+        let entries = state
+            .user_data_history
+            .iter()
+            .filter(|entry| entry.user_id == user_id)
+            .cloned()
+            .collect();
+        Ok(entries)
+    }
+}
+
+#[async_trait]
+impl UserStatusClient for InMemoryPool {
+    async fn put_user_status(&self, user_id: i64, status: UserStatus) -> Result<()> {
+        let fetched_at = current_time_duration().as_secs() as i64;
+        let mut state = self.state.lock().unwrap();
+        let row = state.user_data.entry(user_id).or_insert(UserDataRow {
+            data: None,
+            fetched_at,
+            status,
+        });
+        row.fetched_at = fetched_at;
+        row.status = status;
+        Ok(())
+    }
+
+    async fn get_user_status(&self, user_id: i64) -> Result<Option<UserStatus>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.user_data.get(&user_id).map(|row| row.status))
+    }
+
+    async fn get_user_statuses(&self, user_ids: &[i64]) -> Result<HashMap<i64, UserStatus>> {
+        let state = self.state.lock().unwrap();
+        let statuses = user_ids
+            .iter()
+            .filter_map(|id| Some((*id, state.user_data.get(id)?.status)))
+            .collect();
+        Ok(statuses)
+    }
+}
+
+#[async_trait]
+impl ActionQueueClient for InMemoryPool {
+    async fn enqueue_action(&self, action: &NewQueuedAction<'_>) -> Result<EnqueuedAction> {
+        let mut state = self.state.lock().unwrap();
+        let queued = state.action_queue.values().find(|queued| {
+            queued.account == action.account
+                && queued.action == action.action
+                && queued.user_id == action.user_id
+                && queued.status.is_open()
+        });
+        if let Some(queued) = queued {
+            return Ok(EnqueuedAction {
+                id: queued.id,
+                created: false,
+            });
+        }
+        state.next_queued_action_id += 1;
+        let id = state.next_queued_action_id;
+        state.action_queue.insert(
+            id,
+            QueuedAction {
+                id,
+                account: action.account.to_string(),
+                action: action.action.to_string(),
+                user_id: action.user_id,
+                actor: action.actor.to_string(),
+                reason: action.reason.to_string(),
+                status: QueueStatus::Pending,
+                attempts: 0,
+                last_error: None,
+                next_attempt_at: 0,
+                created_at: action.created_at,
+                updated_at: action.created_at,
+            },
+        );
+        Ok(EnqueuedAction { id, created: true })
+    }
+
+    async fn claim_queued_action(
+        &self,
+        account: &str,
+        actions: &[&str],
+        now: i64,
+    ) -> Result<Option<QueuedAction>> {
+        let mut state = self.state.lock().unwrap();
+        let action = state.action_queue.values_mut().find(|queued| {
+            queued.account == account
+                && actions.contains(&queued.action.as_str())
+                && queued.status == QueueStatus::Pending
+                && queued.next_attempt_at <= now
+        });
+        Ok(action.map(|action| {
+            action.status = QueueStatus::Running;
+            action.attempts += 1;
+            action.updated_at = now;
+            action.clone()
+        }))
+    }
+
+    async fn update_queued_action(
+        &self,
+        id: i64,
+        status: QueueStatus,
+        last_error: Option<&str>,
+        next_attempt_at: i64,
+        now: i64,
+    ) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(action) = state.action_queue.get_mut(&id) {
+            action.status = status;
+            action.last_error = last_error.map(|e| e.to_string());
+            action.next_attempt_at = next_attempt_at;
+            action.updated_at = now;
+        }
+        Ok(())
+    }
+
+    async fn reset_running_actions(&self, account: &str, now: i64) -> Result<u64> {
+        let mut state = self.state.lock().unwrap();
+        let mut count = 0;
+        for action in state.action_queue.values_mut() {
+            if action.account == account && action.status == QueueStatus::Running {
+                action.status = QueueStatus::Pending;
+                action.updated_at = now;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    async fn get_queued_actions(
+        &self,
+        account: &str,
+        filter: &QueueFilter,
+        limit: i64,
+    ) -> Result<Vec<QueuedAction>> {
+        let state = self.state.lock().unwrap();
+        let actions = state
+            .action_queue
+            .values()
+            .rev()
+            .filter(|action| action.account == account && filter.matches(action))
+            .take(limit as usize)
+            .cloned()
+            .collect();
+        Ok(actions)
+    }
+
+    async fn cancel_queued_action(&self, account: &str, id: i64, now: i64) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        match state.action_queue.get_mut(&id) {
+            Some(action) if action.account == account && action.status == QueueStatus::Pending => {
+                action.status = QueueStatus::Cancelled;
+                action.updated_at = now;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn get_open_queued_action(
+        &self,
+        account: &str,
+        action: &str,
+        user_id: i64,
+    ) -> Result<Option<QueuedAction>> {
+        let state = self.state.lock().unwrap();
+        let action = state
+            .action_queue
+            .values()
+            .find(|queued| {
+                queued.account == account
+                    && queued.action == action
+                    && queued.user_id == user_id
+                    && queued.status.is_open()
+            })
+            .cloned();
+        Ok(action)
+    }
+
+    async fn get_queued_actions_by_ids(&self, ids: &[i64]) -> Result<Vec<QueuedAction>> {
+        let state = self.state.lock().unwrap();
+        let actions = state
+            .action_queue
+            .values()
+            .filter(|queued| ids.contains(&queued.id))
+            .cloned()
+            .collect();
+        Ok(actions)
+    }
+
+    async fn reassign_queued_action(
+        &self,
+        id: i64,
+        actor: &str,
+        reason: &str,
+        now: i64,
+    ) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        match state.action_queue.get_mut(&id) {
+            Some(action) if action.status.is_open() => {
+                action.actor = actor.to_string();
+                action.reason = reason.to_string();
+                action.updated_at = now;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn get_dropped_user_ids(
+        &self,
+        account: &str,
+        action: &str,
+        since: i64,
+    ) -> Result<Vec<i64>> {
+        let state = self.state.lock().unwrap();
+        let user_ids = state
+            .action_queue
+            .values()
+            .filter(|queued| {
+                queued.account == account
+                    && queued.action == action
+                    && matches!(queued.status, QueueStatus::Cancelled | QueueStatus::Failed)
+                    && queued.updated_at >= since
+            })
+            .map(|queued| queued.user_id)
+            .collect::<BTreeSet<_>>();
+        Ok(user_ids.into_iter().collect())
+    }
+}
+
+#[async_trait]
+impl RemoveJobClient for InMemoryPool {
+    async fn put_remove_job(
+        &self,
+        account: &str,
+        users: &[RemoveJobUser],
+        created_at: i64,
+    ) -> Result<i64> {
+        let mut state = self.state.lock().unwrap();
+        state.next_remove_job_id += 1;
+        let id = state.next_remove_job_id;
+        let mut users = users.to_vec();
+        users.sort_by_key(|user| user.user_id);
+        state.remove_jobs.insert(
+            id,
+            RemoveJob {
+                id,
+                account: account.to_string(),
+                created_at,
+                users,
+            },
+        );
+        Ok(id)
+    }
+
+    async fn get_remove_job(&self, account: &str, id: i64) -> Result<Option<RemoveJob>> {
+        let state = self.state.lock().unwrap();
+        let job = state
+            .remove_jobs
+            .get(&id)
+            .filter(|job| job.account == account)
+            .cloned();
+        Ok(job)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pretends the ids were last seen a day ago.
+    fn backdate_ids(pool: &InMemoryPool, account: &str, follower: bool) {
+        let mut state = pool.state.lock().unwrap();
+        for row in state.ids_mut(account, follower).values_mut() {
+            row.confirmed_at -= 86400;
+            row.created_at -= 86400;
+        }
+    }
+
+    #[actix::test]
+    async fn test_put_user_ids_confirms_existing_ids() {
+        let pool = InMemoryPool::default();
+        pool.put_user_ids("1", &[1, 2, 3], true).await.unwrap();
+        backdate_ids(&pool, "1", true);
+        let now = current_time_duration().as_secs() as i64;
+
+        pool.put_user_ids("1", &[2, 4], true).await.unwrap();
+        let entries = pool
+            .get_all_user_id_entries("1", true)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.id, entry))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(entries.len(), 4);
+        assert!(entries[&1].confirmed_at < now);
+        assert!(entries[&2].confirmed_at >= now);
+        assert!(entries[&2].created_at < now);
+        assert!(entries[&4].created_at >= now);
+        assert_eq!(entries[&4].confirmed_at, entries[&4].created_at);
+    }
+
+    #[actix::test]
+    async fn test_get_user_ids_filters_by_confirmed_at() {
+        let pool = InMemoryPool::default();
+        pool.put_user_ids("1", &[1, 2], true).await.unwrap();
+        backdate_ids(&pool, "1", true);
+        pool.put_user_ids("1", &[2, 3], true).await.unwrap();
+        pool.put_user_ids("1", &[4], false).await.unwrap();
+        pool.put_user_ids("2", &[5], true).await.unwrap();
+        let now = current_time_duration().as_secs() as i64;
+
+        let one_hour_ago = now - 3600;
+        assert_eq!(
+            pool.get_user_ids("1", true, one_hour_ago).await.unwrap(),
+            vec![2, 3]
+        );
+        assert_eq!(
+            pool.get_user_ids("1", true, now - 2 * 86400).await.unwrap(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            pool.get_user_ids("1", false, one_hour_ago).await.unwrap(),
+            vec![4]
+        );
+        // Only ids confirmed strictly after the given time are returned.
+        assert!(pool.get_user_ids("1", true, now).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+use crate::sql::{FOLLOWERS_IDS, FRIENDS_IDS};
+use anyhow::Result;
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::PgPool;
+use std::collections::BTreeSet;
+
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+pub async fn run_migrations(pool: &PgPool) -> Result<()> {
+    MIGRATOR.run(pool).await?;
+    Ok(())
+}
+
+/// Moves the ids synced before accounts existed, which are stored under an empty account, to
+/// `account`. Ids already synced under `account` are kept. Returns the number of moved rows.
+pub async fn move_legacy_ids(pool: &PgPool, account: &str) -> Result<u64> {
+    let mut tx = pool.begin().await?;
+    let mut moved = 0;
+    for table in [FRIENDS_IDS, FOLLOWERS_IDS] {
+        let query = format!(
+            r"
+            DELETE FROM {table} a
+            WHERE a.account = ''
+            AND EXISTS (SELECT 1 FROM {table} b WHERE b.account = $1 AND b.id = a.id)
+        ",
+            table = table
+        );
+        sqlx::query(&query).bind(account).execute(&mut tx).await?;
+        let query = format!(
+            "UPDATE {table} SET account = $1 WHERE account = ''",
+            table = table
+        );
+        moved += sqlx::query(&query)
+            .bind(account)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+    }
+    tx.commit().await?;
+    Ok(moved)
+}
+
+pub async fn get_migration_status(pool: &PgPool) -> Result<Vec<MigrationStatus>> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied_versions = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect::<BTreeSet<_>>();
+
+    let status = MIGRATOR
+        .iter()
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied_versions.contains(&migration.version),
+        })
+        .collect();
+    Ok(status)
+}
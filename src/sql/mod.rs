@@ -2,40 +2,98 @@ use crate::current_time_duration;
 use anyhow::Result;
 use async_trait::async_trait;
 use egg_mode::user::TwitterUser;
+use serde_json::Value;
 use sqlx::postgres::PgRow;
 use sqlx::types::Json;
 use sqlx::{PgPool, Row};
+use std::collections::HashMap;
 
+mod action_queue;
+mod actions;
+mod allowlist;
+mod blocklist;
+mod deferred_users;
+mod memory;
+mod migration;
+mod relationship_events;
+mod remove_jobs;
+mod skipped_users;
+mod snapshots;
+mod user_history;
 mod user_ids;
+mod user_status;
+mod webhook_cursors;
+pub use action_queue::{
+    ActionQueueClient, EnqueuedAction, NewQueuedAction, QueueFilter, QueueStatus, QueuedAction,
+};
+pub use actions::{ActionEntry, ActionFilter, ActionLogClient, NewAction};
+pub use allowlist::{AllowedUser, AllowlistClient};
+pub use blocklist::{BlockedUser, BlocklistClient};
+pub use deferred_users::{DeferredUser, DeferredUserClient};
+pub use memory::InMemoryPool;
+pub use migration::{get_migration_status, move_legacy_ids, run_migrations, MigrationStatus};
+pub use relationship_events::{
+    relationship_events, RelationshipEvent, RelationshipEventClient, RelationshipEventFilter,
+    FOLLOW, FOLLOWED_BY, UNFOLLOW, UNFOLLOWED_BY,
+};
+pub use remove_jobs::{RemoveJob, RemoveJobClient, RemoveJobUser};
+pub use skipped_users::{SkippedUser, SkippedUserClient};
+pub use snapshots::{Snapshot, SnapshotClient};
+use user_history::{profile_json, put_user_history};
+pub use user_history::{UserHistoryClient, UserHistoryEntry};
 pub use user_ids::{UserIdClient, UserIdEntry};
+pub use user_status::{UserStatus, UserStatusClient};
+pub use webhook_cursors::WebhookCursorClient;
 
 const FRIENDS_IDS: &str = "friends_ids";
 const FOLLOWERS_IDS: &str = "followers_ids";
 
 #[async_trait]
 pub trait PgPoolExt {
-    async fn put_user_ids(&self, ids: &[u64], follower: bool) -> Result<()>;
-    async fn get_user_ids(&self, follower: bool, confirmed_after: i64) -> Result<Vec<i64>>;
+    async fn put_user_ids(&self, account: &str, ids: &[u64], follower: bool) -> Result<()>;
+    async fn get_user_ids(
+        &self,
+        account: &str,
+        follower: bool,
+        confirmed_after: i64,
+    ) -> Result<Vec<i64>>;
 
     async fn get_user_info(&self, id: i64) -> Result<Option<TwitterUser>>;
+    /// Profiles of the given users that have one, keyed by user id.
+    async fn get_user_infos(&self, ids: &[i64]) -> Result<HashMap<i64, TwitterUser>>;
     async fn put_user_info(&self, user: &TwitterUser) -> Result<()>;
 
-    async fn get_no_data_user_ids(&self, confirmed_after: i64, size: i64) -> Result<Vec<i64>>;
+    async fn get_no_data_user_ids(
+        &self,
+        account: &str,
+        confirmed_after: i64,
+        size: i64,
+    ) -> Result<Vec<i64>>;
+
+    /// Friends and followers whose profile was fetched before `fetched_before`, oldest first.
+    async fn get_stale_user_ids(
+        &self,
+        account: &str,
+        confirmed_after: i64,
+        fetched_before: i64,
+        size: i64,
+    ) -> Result<Vec<i64>>;
 }
 
 #[async_trait]
 impl PgPoolExt for PgPool {
-    async fn put_user_ids(&self, ids: &[u64], follower: bool) -> Result<()> {
+    async fn put_user_ids(&self, account: &str, ids: &[u64], follower: bool) -> Result<()> {
         let table_name = if follower { FOLLOWERS_IDS } else { FRIENDS_IDS };
         let query = format!(
             r"
-            INSERT INTO {table_name} (id, confirmed_at, created_at)
+            INSERT INTO {table_name} (account, id, confirmed_at, created_at)
             VALUES (
-                UNNEST($1::BIGINT[]),
-                $2,
-                $2
+                $1,
+                UNNEST($2::BIGINT[]),
+                $3,
+                $3
             )
-            ON CONFLICT (id)
+            ON CONFLICT (account, id)
             DO UPDATE SET confirmed_at = EXCLUDED.confirmed_at
         ",
             table_name = table_name
@@ -46,6 +104,7 @@ impl PgPoolExt for PgPool {
         let ids = ids.iter().map(|&id| id as i64).collect::<Vec<_>>();
         for ids in ids.chunks(CHUNK_SIZE) {
             sqlx::query(&query)
+                .bind(account)
                 .bind(ids)
                 .bind(unixtime_second as i64)
                 .execute(self)
@@ -53,16 +112,22 @@ impl PgPoolExt for PgPool {
         }
         Ok(())
     }
-    async fn get_user_ids(&self, follower: bool, confirmed_after: i64) -> Result<Vec<i64>> {
+    async fn get_user_ids(
+        &self,
+        account: &str,
+        follower: bool,
+        confirmed_after: i64,
+    ) -> Result<Vec<i64>> {
         let table_name = if follower { FOLLOWERS_IDS } else { FRIENDS_IDS };
         let query = format!(
             r"
             SELECT id FROM {table_name}
-            WHERE confirmed_at > $1
+            WHERE account = $1 AND confirmed_at > $2
         ",
             table_name = table_name
         );
         let ids = sqlx::query(&query)
+            .bind(account)
             .bind(confirmed_after)
             .try_map(|row: PgRow| row.try_get::<i64, _>("id"))
             .fetch_all(self)
@@ -73,7 +138,7 @@ impl PgPoolExt for PgPool {
     async fn get_user_info(&self, id: i64) -> Result<Option<TwitterUser>> {
         let result = sqlx::query(
             r"
-        SELECT data FROM user_data WHERE id=$1
+        SELECT data FROM user_data WHERE id=$1 AND data IS NOT NULL
         ",
         )
         .bind(id)
@@ -89,31 +154,71 @@ impl PgPoolExt for PgPool {
         }
     }
 
+    async fn get_user_infos(&self, ids: &[i64]) -> Result<HashMap<i64, TwitterUser>> {
+        let rows = sqlx::query(
+            r"
+        SELECT id, data FROM user_data WHERE id = ANY($1) AND data IS NOT NULL
+        ",
+        )
+        .bind(ids)
+        .try_map(|row: PgRow| {
+            let id = row.try_get::<i64, _>("id")?;
+            let data = row.try_get::<Json<Value>, _>("data")?;
+            Ok((id, data.0))
+        })
+        .fetch_all(self)
+        .await?;
+        let mut users = HashMap::with_capacity(rows.len());
+        for (id, data) in rows {
+            match serde_json::from_value(data) {
+                Ok(user) => {
+                    users.insert(id, user);
+                }
+                Err(e) => log::error!("Failed to parse user_data of id={}: {:?}", id, e),
+            }
+        }
+        Ok(users)
+    }
+
     async fn put_user_info(&self, user: &TwitterUser) -> Result<()> {
         let id = user.id as i64;
+        let fetched_at = current_time_duration().as_secs() as i64;
+        let mut tx = self.begin().await?;
         sqlx::query(
             r"
-            INSERT INTO user_data (id, data) VALUES ($1, $2)
+            INSERT INTO user_data (id, data, fetched_at, status) VALUES ($1, $2, $3, 'active')
             ON CONFLICT (id)
-            DO UPDATE SET data = EXCLUDED.data
+            DO UPDATE SET
+                data = EXCLUDED.data,
+                fetched_at = EXCLUDED.fetched_at,
+                status = EXCLUDED.status
         ",
         )
         .bind(id)
         .bind(Json(user))
-        .execute(self)
+        .bind(fetched_at)
+        .execute(&mut tx)
         .await?;
+        put_user_history(&mut tx, id, &profile_json(user)?, fetched_at).await?;
+        tx.commit().await?;
         Ok(())
     }
 
-    async fn get_no_data_user_ids(&self, confirmed_after: i64, size: i64) -> Result<Vec<i64>> {
+    async fn get_no_data_user_ids(
+        &self,
+        account: &str,
+        confirmed_after: i64,
+        size: i64,
+    ) -> Result<Vec<i64>> {
         let mut no_data_friends_ids = sqlx::query(
             r"
             SELECT friends_ids.id FROM friends_ids
             LEFT JOIN user_data ON user_data.id = friends_ids.id
-            WHERE user_data.data IS NULL AND confirmed_at > $1
-            LIMIT $2
+            WHERE user_data.id IS NULL AND account = $1 AND confirmed_at > $2
+            LIMIT $3
         ",
         )
+        .bind(account)
         .bind(confirmed_after)
         .bind(size)
         .try_map(|row: PgRow| row.try_get::<i64, _>(0))
@@ -123,9 +228,11 @@ impl PgPoolExt for PgPool {
             r"
             SELECT followers_ids.id FROM followers_ids
             LEFT JOIN user_data ON user_data.id = followers_ids.id
-            WHERE user_data.data IS NULL AND confirmed_at > $1
-            LIMIT $2        ",
+            WHERE user_data.id IS NULL AND account = $1 AND confirmed_at > $2
+            LIMIT $3
+        ",
         )
+        .bind(account)
         .bind(confirmed_after)
         .bind(size)
         .try_map(|row: PgRow| row.try_get::<i64, _>(0))
@@ -134,4 +241,39 @@ impl PgPoolExt for PgPool {
         no_data_friends_ids.extend(no_data_followers_ids);
         Ok(no_data_friends_ids)
     }
+
+    async fn get_stale_user_ids(
+        &self,
+        account: &str,
+        confirmed_after: i64,
+        fetched_before: i64,
+        size: i64,
+    ) -> Result<Vec<i64>> {
+        let ids = sqlx::query(
+            r"
+            SELECT user_data.id FROM user_data
+            WHERE user_data.status = 'active' AND user_data.fetched_at < $3
+            AND (
+                EXISTS (
+                    SELECT 1 FROM friends_ids
+                    WHERE friends_ids.id = user_data.id AND account = $1 AND confirmed_at > $2
+                )
+                OR EXISTS (
+                    SELECT 1 FROM followers_ids
+                    WHERE followers_ids.id = user_data.id AND account = $1 AND confirmed_at > $2
+                )
+            )
+            ORDER BY user_data.fetched_at
+            LIMIT $4
+        ",
+        )
+        .bind(account)
+        .bind(confirmed_after)
+        .bind(fetched_before)
+        .bind(size)
+        .try_map(|row: PgRow| row.try_get::<i64, _>(0))
+        .fetch_all(self)
+        .await?;
+        Ok(ids)
+    }
 }
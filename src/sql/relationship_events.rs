@@ -0,0 +1,232 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Postgres, Row, Transaction};
+use std::collections::HashMap;
+
+pub const FOLLOW: &str = "follow";
+pub const UNFOLLOW: &str = "unfollow";
+pub const FOLLOWED_BY: &str = "followed_by";
+pub const UNFOLLOWED_BY: &str = "unfollowed_by";
+
+/// The events marking the start and the end of a relationship in the given direction.
+pub fn relationship_events(follower: bool) -> (&'static str, &'static str) {
+    if follower {
+        (FOLLOWED_BY, UNFOLLOWED_BY)
+    } else {
+        (FOLLOW, UNFOLLOW)
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct RelationshipEvent {
+    pub id: i64,
+    pub account: String,
+    pub user_id: i64,
+    pub event: String,
+    pub created_at: i64,
+}
+
+#[derive(Deserialize, Default)]
+pub struct RelationshipEventFilter {
+    pub event: Option<String>,
+    pub user_id: Option<i64>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub before_id: Option<i64>,
+}
+
+impl RelationshipEventFilter {
+    pub(crate) fn matches(&self, entry: &RelationshipEvent) -> bool {
+        self.event
+            .as_ref()
+            .is_none_or(|event| event == &entry.event)
+            && self.user_id.is_none_or(|id| id == entry.user_id)
+            && self.since.is_none_or(|since| since <= entry.created_at)
+            && self.until.is_none_or(|until| entry.created_at < until)
+            && self.before_id.is_none_or(|before_id| entry.id < before_id)
+    }
+}
+
+/// Records `event` for each of `user_ids`. Only written together with the snapshot they were
+/// found in, see `SnapshotClient::finish_snapshot`.
+pub(crate) async fn put_relationship_events(
+    tx: &mut Transaction<'_, Postgres>,
+    account: &str,
+    event: &str,
+    user_ids: &[i64],
+    created_at: i64,
+) -> Result<()> {
+    const CHUNK_SIZE: usize = 1000;
+    for user_ids in user_ids.chunks(CHUNK_SIZE) {
+        sqlx::query(
+            r"
+            INSERT INTO relationship_events (account, user_id, event, created_at)
+            VALUES ($1, UNNEST($2::BIGINT[]), $3, $4)
+        ",
+        )
+        .bind(account)
+        .bind(user_ids)
+        .bind(event)
+        .bind(created_at)
+        .execute(&mut *tx)
+        .await?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+pub trait RelationshipEventClient {
+    /// Users whose latest event in the given direction started the relationship.
+    async fn get_active_relationships(&self, account: &str, follower: bool) -> Result<Vec<i64>>;
+    /// When each of the given relationships last started, for users that have a start event.
+    async fn get_relationship_started_at(
+        &self,
+        account: &str,
+        follower: bool,
+        user_ids: &[i64],
+    ) -> Result<HashMap<i64, i64>>;
+
+    async fn get_relationship_events(
+        &self,
+        account: &str,
+        filter: &RelationshipEventFilter,
+        limit: i64,
+    ) -> Result<Vec<RelationshipEvent>>;
+
+    /// Events with ids above `after_id`, oldest first.
+    async fn get_relationship_events_after(
+        &self,
+        account: &str,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<RelationshipEvent>>;
+}
+
+#[async_trait]
+impl RelationshipEventClient for PgPool {
+    async fn get_active_relationships(&self, account: &str, follower: bool) -> Result<Vec<i64>> {
+        let (started, ended) = relationship_events(follower);
+        let user_ids = sqlx::query(
+            r"
+            SELECT user_id FROM (
+                SELECT DISTINCT ON (user_id) user_id, event
+                FROM relationship_events
+                WHERE account = $1 AND event IN ($2, $3)
+                ORDER BY user_id, id DESC
+            ) latest
+            WHERE event = $2
+        ",
+        )
+        .bind(account)
+        .bind(started)
+        .bind(ended)
+        .try_map(|row: PgRow| row.try_get::<i64, _>("user_id"))
+        .fetch_all(self)
+        .await?;
+        Ok(user_ids)
+    }
+
+    async fn get_relationship_started_at(
+        &self,
+        account: &str,
+        follower: bool,
+        user_ids: &[i64],
+    ) -> Result<HashMap<i64, i64>> {
+        let (started, _) = relationship_events(follower);
+        let started_at = sqlx::query(
+            r"
+            SELECT DISTINCT ON (user_id) user_id, created_at
+            FROM relationship_events
+            WHERE account = $1 AND event = $2 AND user_id = ANY($3)
+            ORDER BY user_id, id DESC
+        ",
+        )
+        .bind(account)
+        .bind(started)
+        .bind(user_ids)
+        .try_map(|row: PgRow| {
+            Ok((
+                row.try_get::<i64, _>("user_id")?,
+                row.try_get::<i64, _>("created_at")?,
+            ))
+        })
+        .fetch_all(self)
+        .await?;
+        Ok(started_at.into_iter().collect())
+    }
+
+    async fn get_relationship_events(
+        &self,
+        account: &str,
+        filter: &RelationshipEventFilter,
+        limit: i64,
+    ) -> Result<Vec<RelationshipEvent>> {
+        let events = sqlx::query(
+            r"
+            SELECT id, account, user_id, event, created_at
+            FROM relationship_events
+            WHERE account = $1
+            AND ($2::TEXT IS NULL OR event = $2)
+            AND ($3::BIGINT IS NULL OR user_id = $3)
+            AND ($4::BIGINT IS NULL OR created_at >= $4)
+            AND ($5::BIGINT IS NULL OR created_at < $5)
+            AND ($6::BIGINT IS NULL OR id < $6)
+            ORDER BY id DESC
+            LIMIT $7
+        ",
+        )
+        .bind(account)
+        .bind(filter.event.as_deref())
+        .bind(filter.user_id)
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(filter.before_id)
+        .bind(limit)
+        .try_map(|row: PgRow| {
+            Ok(RelationshipEvent {
+                id: row.try_get("id")?,
+                account: row.try_get("account")?,
+                user_id: row.try_get("user_id")?,
+                event: row.try_get("event")?,
+                created_at: row.try_get("created_at")?,
+            })
+        })
+        .fetch_all(self)
+        .await?;
+        Ok(events)
+    }
+
+    async fn get_relationship_events_after(
+        &self,
+        account: &str,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<RelationshipEvent>> {
+        let events = sqlx::query(
+            r"
+            SELECT id, account, user_id, event, created_at
+            FROM relationship_events
+            WHERE account = $1 AND id > $2
+            ORDER BY id
+            LIMIT $3
+        ",
+        )
+        .bind(account)
+        .bind(after_id)
+        .bind(limit)
+        .try_map(|row: PgRow| {
+            Ok(RelationshipEvent {
+                id: row.try_get("id")?,
+                account: row.try_get("account")?,
+                user_id: row.try_get("user_id")?,
+                event: row.try_get("event")?,
+                created_at: row.try_get("created_at")?,
+            })
+        })
+        .fetch_all(self)
+        .await?;
+        Ok(events)
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+/// A user in a bulk removal, either queued as `action_id` or skipped without being queued.
+#[derive(Serialize, Clone)]
+pub struct RemoveJobUser {
+    pub user_id: i64,
+    pub action_id: Option<i64>,
+    pub skipped_reason: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct RemoveJob {
+    pub id: i64,
+    pub account: String,
+    pub created_at: i64,
+    pub users: Vec<RemoveJobUser>,
+}
+
+#[async_trait]
+pub trait RemoveJobClient {
+    async fn put_remove_job(
+        &self,
+        account: &str,
+        users: &[RemoveJobUser],
+        created_at: i64,
+    ) -> Result<i64>;
+    async fn get_remove_job(&self, account: &str, id: i64) -> Result<Option<RemoveJob>>;
+}
+
+#[async_trait]
+impl RemoveJobClient for PgPool {
+    async fn put_remove_job(
+        &self,
+        account: &str,
+        users: &[RemoveJobUser],
+        created_at: i64,
+    ) -> Result<i64> {
+        let mut tx = self.begin().await?;
+        let id = sqlx::query(
+            r"
+            INSERT INTO remove_jobs (account, created_at) VALUES ($1, $2) RETURNING id
+        ",
+        )
+        .bind(account)
+        .bind(created_at)
+        .try_map(|row: PgRow| row.try_get::<i64, _>("id"))
+        .fetch_one(&mut tx)
+        .await?;
+        for user in users {
+            sqlx::query(
+                r"
+                INSERT INTO remove_job_users (job_id, user_id, action_id, skipped_reason)
+                VALUES ($1, $2, $3, $4)
+            ",
+            )
+            .bind(id)
+            .bind(user.user_id)
+            .bind(user.action_id)
+            .bind(user.skipped_reason.as_deref())
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn get_remove_job(&self, account: &str, id: i64) -> Result<Option<RemoveJob>> {
+        let created_at = sqlx::query(
+            r"
+            SELECT created_at FROM remove_jobs WHERE account = $1 AND id = $2
+        ",
+        )
+        .bind(account)
+        .bind(id)
+        .try_map(|row: PgRow| row.try_get::<i64, _>("created_at"))
+        .fetch_optional(self)
+        .await?;
+        let created_at = match created_at {
+            Some(created_at) => created_at,
+            None => return Ok(None),
+        };
+        let users = sqlx::query(
+            r"
+            SELECT user_id, action_id, skipped_reason FROM remove_job_users
+            WHERE job_id = $1
+            ORDER BY user_id
+        ",
+        )
+        .bind(id)
+        .try_map(|row: PgRow| {
+            Ok(RemoveJobUser {
+                user_id: row.try_get("user_id")?,
+                action_id: row.try_get("action_id")?,
+                skipped_reason: row.try_get("skipped_reason")?,
+            })
+        })
+        .fetch_all(self)
+        .await?;
+        Ok(Some(RemoveJob {
+            id,
+            account: account.to_string(),
+            created_at,
+            users,
+        }))
+    }
+}
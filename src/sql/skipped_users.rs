@@ -0,0 +1,60 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+#[derive(Serialize, Clone)]
+pub struct SkippedUser {
+    pub user_id: i64,
+    pub reason: String,
+    pub skipped_at: i64,
+}
+
+#[async_trait]
+pub trait SkippedUserClient {
+    async fn put_skipped_user(&self, account: &str, user: &SkippedUser) -> Result<()>;
+    async fn get_skipped_users(&self, account: &str) -> Result<Vec<SkippedUser>>;
+}
+
+#[async_trait]
+impl SkippedUserClient for PgPool {
+    async fn put_skipped_user(&self, account: &str, user: &SkippedUser) -> Result<()> {
+        sqlx::query(
+            r"
+            INSERT INTO follow_back_skips (account, user_id, reason, skipped_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (account, user_id)
+            DO UPDATE SET reason = EXCLUDED.reason, skipped_at = EXCLUDED.skipped_at
+        ",
+        )
+        .bind(account)
+        .bind(user.user_id)
+        .bind(&user.reason)
+        .bind(user.skipped_at)
+        .execute(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_skipped_users(&self, account: &str) -> Result<Vec<SkippedUser>> {
+        let users = sqlx::query(
+            r"
+            SELECT user_id, reason, skipped_at FROM follow_back_skips
+            WHERE account = $1
+            ORDER BY skipped_at DESC
+        ",
+        )
+        .bind(account)
+        .try_map(|row: PgRow| {
+            Ok(SkippedUser {
+                user_id: row.try_get("user_id")?,
+                reason: row.try_get("reason")?,
+                skipped_at: row.try_get("skipped_at")?,
+            })
+        })
+        .fetch_all(self)
+        .await?;
+        Ok(users)
+    }
+}
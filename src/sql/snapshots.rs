@@ -0,0 +1,198 @@
+use crate::sql::relationship_events::put_relationship_events;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+/// The ids fetched during one full cursor cycle of `UserIdSynchronizer`.
+#[derive(Serialize, Clone)]
+pub struct Snapshot {
+    pub id: i64,
+    pub account: String,
+    pub follower: bool,
+    pub started_at: i64,
+    /// `None` until the last page has been fetched.
+    pub finished_at: Option<i64>,
+}
+
+#[async_trait]
+pub trait SnapshotClient {
+    async fn start_snapshot(&self, account: &str, follower: bool, started_at: i64) -> Result<i64>;
+    async fn put_snapshot_members(&self, snapshot_id: i64, user_ids: &[i64]) -> Result<()>;
+    /// Marks the snapshot complete and records the relationship `events` found in it, given as
+    /// pairs of an event and the user ids, all at once.
+    async fn finish_snapshot(
+        &self,
+        snapshot_id: i64,
+        account: &str,
+        events: &[(&str, &[i64])],
+        finished_at: i64,
+    ) -> Result<()>;
+
+    /// The latest finished snapshot with an id below `before_id`, if given.
+    async fn get_complete_snapshot(
+        &self,
+        account: &str,
+        follower: bool,
+        before_id: Option<i64>,
+    ) -> Result<Option<Snapshot>>;
+    async fn get_snapshot_members(&self, snapshot_id: i64) -> Result<Vec<i64>>;
+    /// Members of `snapshot_id` that are not in `excluded_snapshot_id`, in ascending order.
+    async fn get_snapshot_difference(
+        &self,
+        snapshot_id: i64,
+        excluded_snapshot_id: i64,
+    ) -> Result<Vec<i64>>;
+
+    /// Deletes the snapshots of the account in the direction with ids below `before_id`.
+    async fn delete_snapshots(&self, account: &str, follower: bool, before_id: i64) -> Result<()>;
+}
+
+#[async_trait]
+impl SnapshotClient for PgPool {
+    async fn start_snapshot(&self, account: &str, follower: bool, started_at: i64) -> Result<i64> {
+        let id = sqlx::query(
+            r"
+            INSERT INTO snapshots (account, follower, started_at)
+            VALUES ($1, $2, $3)
+            RETURNING id
+        ",
+        )
+        .bind(account)
+        .bind(follower)
+        .bind(started_at)
+        .try_map(|row: PgRow| row.try_get::<i64, _>("id"))
+        .fetch_one(self)
+        .await?;
+        Ok(id)
+    }
+
+    async fn put_snapshot_members(&self, snapshot_id: i64, user_ids: &[i64]) -> Result<()> {
+        const CHUNK_SIZE: usize = 1000;
+        for user_ids in user_ids.chunks(CHUNK_SIZE) {
+            sqlx::query(
+                r"
+                INSERT INTO snapshot_members (snapshot_id, user_id)
+                VALUES ($1, UNNEST($2::BIGINT[]))
+                ON CONFLICT DO NOTHING
+            ",
+            )
+            .bind(snapshot_id)
+            .bind(user_ids)
+            .execute(self)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn finish_snapshot(
+        &self,
+        snapshot_id: i64,
+        account: &str,
+        events: &[(&str, &[i64])],
+        finished_at: i64,
+    ) -> Result<()> {
+        let mut tx = self.begin().await?;
+        for (event, user_ids) in events {
+            put_relationship_events(&mut tx, account, event, user_ids, finished_at).await?;
+        }
+        sqlx::query(
+            r"
+            UPDATE snapshots SET finished_at = $2 WHERE id = $1
+        ",
+        )
+        .bind(snapshot_id)
+        .bind(finished_at)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_complete_snapshot(
+        &self,
+        account: &str,
+        follower: bool,
+        before_id: Option<i64>,
+    ) -> Result<Option<Snapshot>> {
+        let snapshot = sqlx::query(
+            r"
+            SELECT id, account, follower, started_at, finished_at
+            FROM snapshots
+            WHERE account = $1
+            AND follower = $2
+            AND finished_at IS NOT NULL
+            AND ($3::BIGINT IS NULL OR id < $3)
+            ORDER BY id DESC
+            LIMIT 1
+        ",
+        )
+        .bind(account)
+        .bind(follower)
+        .bind(before_id)
+        .try_map(|row: PgRow| {
+            Ok(Snapshot {
+                id: row.try_get("id")?,
+                account: row.try_get("account")?,
+                follower: row.try_get("follower")?,
+                started_at: row.try_get("started_at")?,
+                finished_at: row.try_get("finished_at")?,
+            })
+        })
+        .fetch_optional(self)
+        .await?;
+        Ok(snapshot)
+    }
+
+    async fn get_snapshot_members(&self, snapshot_id: i64) -> Result<Vec<i64>> {
+        let user_ids = sqlx::query(
+            r"
+            SELECT user_id FROM snapshot_members WHERE snapshot_id = $1
+        ",
+        )
+        .bind(snapshot_id)
+        .try_map(|row: PgRow| row.try_get::<i64, _>("user_id"))
+        .fetch_all(self)
+        .await?;
+        Ok(user_ids)
+    }
+
+    async fn get_snapshot_difference(
+        &self,
+        snapshot_id: i64,
+        excluded_snapshot_id: i64,
+    ) -> Result<Vec<i64>> {
+        let user_ids = sqlx::query(
+            r"
+            SELECT a.user_id FROM snapshot_members a
+            WHERE a.snapshot_id = $1
+            AND NOT EXISTS (
+                SELECT 1 FROM snapshot_members b
+                WHERE b.snapshot_id = $2 AND b.user_id = a.user_id
+            )
+            ORDER BY a.user_id
+        ",
+        )
+        .bind(snapshot_id)
+        .bind(excluded_snapshot_id)
+        .try_map(|row: PgRow| row.try_get::<i64, _>("user_id"))
+        .fetch_all(self)
+        .await?;
+        Ok(user_ids)
+    }
+
+    async fn delete_snapshots(&self, account: &str, follower: bool, before_id: i64) -> Result<()> {
+        sqlx::query(
+            r"
+            DELETE FROM snapshots WHERE account = $1 AND follower = $2 AND id < $3
+        ",
+        )
+        .bind(account)
+        .bind(follower)
+        .bind(before_id)
+        .execute(self)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use egg_mode::user::TwitterUser;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::postgres::PgRow;
+use sqlx::types::Json;
+use sqlx::{PgPool, Postgres, Row, Transaction};
+
+/// A version of a user's profile, stored whenever `put_user_info` sees a change.
+#[derive(Serialize, Clone)]
+pub struct UserHistoryEntry {
+    pub id: i64,
+    pub user_id: i64,
+    pub data: Value,
+    pub fetched_at: i64,
+}
+
+/// Fields left out of the history because they change with every tweet or like, which would
+/// otherwise make a new version on each refresh of an active user.
+const VOLATILE_FIELDS: &[&str] = &["status", "statuses_count", "favourites_count"];
+
+/// The profile fields of `user` as stored in the history.
+pub(crate) fn profile_json(user: &TwitterUser) -> Result<Value> {
+    let mut value = serde_json::to_value(user)?;
+    if let Value::Object(fields) = &mut value {
+        for field in VOLATILE_FIELDS {
+            fields.remove(*field);
+        }
+    }
+    Ok(value)
+}
+
+#[async_trait]
+pub trait UserHistoryClient {
+    /// Versions of the profile of `user_id`, oldest first.
+    async fn get_user_history(&self, user_id: i64) -> Result<Vec<UserHistoryEntry>>;
+}
+
+/// Appends `profile` to the history unless it equals the latest version.
+pub(crate) async fn put_user_history(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i64,
+    profile: &Value,
+    fetched_at: i64,
+) -> Result<()> {
+    sqlx::query(
+        r"
+        INSERT INTO user_data_history (user_id, data, fetched_at)
+        SELECT $1, $2, $3
+        WHERE NOT EXISTS (
+            SELECT 1 FROM (
+                SELECT data FROM user_data_history
+                WHERE user_id = $1
+                ORDER BY id DESC
+                LIMIT 1
+            ) latest
+            WHERE latest.data = $2
+        )
+    ",
+    )
+    .bind(user_id)
+    .bind(Json(profile))
+    .bind(fetched_at)
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+#[async_trait]
+impl UserHistoryClient for PgPool {
+    async fn get_user_history(&self, user_id: i64) -> Result<Vec<UserHistoryEntry>> {
+        let entries = sqlx::query(
+            r"
+            SELECT id, user_id, data, fetched_at FROM user_data_history
+            WHERE user_id = $1
+            ORDER BY id
+        ",
+        )
+        .bind(user_id)
+        .try_map(|row: PgRow| {
+            Ok(UserHistoryEntry {
+                id: row.try_get("id")?,
+                user_id: row.try_get("user_id")?,
+                data: row.try_get::<Json<Value>, _>("data")?.0,
+                fetched_at: row.try_get("fetched_at")?,
+            })
+        })
+        .fetch_all(self)
+        .await?;
+        Ok(entries)
+    }
+}
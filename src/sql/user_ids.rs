@@ -14,20 +14,30 @@ pub struct UserIdEntry {
 
 #[async_trait]
 pub trait UserIdClient {
-    async fn get_all_user_id_entries(&self, follower: bool) -> Result<Vec<UserIdEntry>>;
+    async fn get_all_user_id_entries(
+        &self,
+        account: &str,
+        follower: bool,
+    ) -> Result<Vec<UserIdEntry>>;
 }
 
 #[async_trait]
 impl UserIdClient for PgPool {
-    async fn get_all_user_id_entries(&self, follower: bool) -> Result<Vec<UserIdEntry>> {
+    async fn get_all_user_id_entries(
+        &self,
+        account: &str,
+        follower: bool,
+    ) -> Result<Vec<UserIdEntry>> {
         let table_name = if follower { FOLLOWERS_IDS } else { FRIENDS_IDS };
         let query = format!(
             r"
             SELECT id, confirmed_at, created_at FROM {table_name}
+            WHERE account = $1
         ",
             table_name = table_name
         );
         let ids = sqlx::query(&query)
+            .bind(account)
             .try_map(|row: PgRow| {
                 let id: i64 = row.try_get("id")?;
                 let confirmed_at: i64 = row.try_get("confirmed_at")?;
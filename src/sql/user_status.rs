@@ -0,0 +1,100 @@
+use crate::current_time_duration;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserStatus {
+    Active,
+    Suspended,
+    /// The account existed when its profile was fetched and has been gone since.
+    Deactivated,
+    /// The account was gone before its profile could ever be fetched.
+    NotFound,
+}
+
+impl UserStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserStatus::Active => "active",
+            UserStatus::Suspended => "suspended",
+            UserStatus::Deactivated => "deactivated",
+            UserStatus::NotFound => "not_found",
+        }
+    }
+
+    pub fn parse(status: &str) -> Result<Self> {
+        match status {
+            "active" => Ok(UserStatus::Active),
+            "suspended" => Ok(UserStatus::Suspended),
+            "deactivated" => Ok(UserStatus::Deactivated),
+            "not_found" => Ok(UserStatus::NotFound),
+            _ => Err(anyhow!("Unknown user status: {}", status)),
+        }
+    }
+}
+
+/// Status of the accounts in `user_data`. `put_user_info` marks a user active; the other
+/// statuses keep the last fetched profile, if any.
+#[async_trait]
+pub trait UserStatusClient {
+    async fn put_user_status(&self, user_id: i64, status: UserStatus) -> Result<()>;
+    async fn get_user_status(&self, user_id: i64) -> Result<Option<UserStatus>>;
+    /// Statuses of the users in `user_data` among `user_ids`.
+    async fn get_user_statuses(&self, user_ids: &[i64]) -> Result<HashMap<i64, UserStatus>>;
+}
+
+#[async_trait]
+impl UserStatusClient for PgPool {
+    async fn put_user_status(&self, user_id: i64, status: UserStatus) -> Result<()> {
+        sqlx::query(
+            r"
+            INSERT INTO user_data (id, fetched_at, status) VALUES ($1, $2, $3)
+            ON CONFLICT (id)
+            DO UPDATE SET fetched_at = EXCLUDED.fetched_at, status = EXCLUDED.status
+        ",
+        )
+        .bind(user_id)
+        .bind(current_time_duration().as_secs() as i64)
+        .bind(status.as_str())
+        .execute(self)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_user_status(&self, user_id: i64) -> Result<Option<UserStatus>> {
+        let status = sqlx::query(
+            r"
+            SELECT status FROM user_data WHERE id = $1
+        ",
+        )
+        .bind(user_id)
+        .try_map(|row: PgRow| row.try_get::<String, _>("status"))
+        .fetch_optional(self)
+        .await?;
+        status.map(|status| UserStatus::parse(&status)).transpose()
+    }
+
+    async fn get_user_statuses(&self, user_ids: &[i64]) -> Result<HashMap<i64, UserStatus>> {
+        let rows = sqlx::query(
+            r"
+            SELECT id, status FROM user_data WHERE id = ANY($1)
+        ",
+        )
+        .bind(user_ids)
+        .try_map(|row: PgRow| {
+            let id = row.try_get::<i64, _>("id")?;
+            let status = row.try_get::<String, _>("status")?;
+            Ok((id, status))
+        })
+        .fetch_all(self)
+        .await?;
+        rows.into_iter()
+            .map(|(id, status)| Ok((id, UserStatus::parse(&status)?)))
+            .collect()
+    }
+}
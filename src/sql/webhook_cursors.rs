@@ -0,0 +1,45 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+/// The id of the last relationship event delivered to each webhook.
+#[async_trait]
+pub trait WebhookCursorClient {
+    async fn get_webhook_cursor(&self, account: &str, url: &str) -> Result<Option<i64>>;
+    async fn put_webhook_cursor(&self, account: &str, url: &str, last_event_id: i64) -> Result<()>;
+}
+
+#[async_trait]
+impl WebhookCursorClient for PgPool {
+    async fn get_webhook_cursor(&self, account: &str, url: &str) -> Result<Option<i64>> {
+        let last_event_id = sqlx::query(
+            r"
+            SELECT last_event_id FROM webhook_cursors WHERE account = $1 AND url = $2
+        ",
+        )
+        .bind(account)
+        .bind(url)
+        .try_map(|row: PgRow| row.try_get::<i64, _>("last_event_id"))
+        .fetch_optional(self)
+        .await?;
+        Ok(last_event_id)
+    }
+
+    async fn put_webhook_cursor(&self, account: &str, url: &str, last_event_id: i64) -> Result<()> {
+        sqlx::query(
+            r"
+            INSERT INTO webhook_cursors (account, url, last_event_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (account, url)
+            DO UPDATE SET last_event_id = EXCLUDED.last_event_id
+        ",
+        )
+        .bind(account)
+        .bind(url)
+        .bind(last_event_id)
+        .execute(self)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+//! Fixtures shared by the unit tests.
+use crate::sql::SnapshotClient;
+use anyhow::Result;
+use egg_mode::user::TwitterUser;
+use serde_json::json;
+
+/// A public account created in 2018 that has never tweeted.
+pub(crate) fn user(id: u64, screen_name: &str) -> TwitterUser {
+    serde_json::from_value(json!({
+        "contributors_enabled": false,
+        "created_at": "Wed Oct 10 20:19:24 +0000 2018",
+        "default_profile": false,
+        "default_profile_image": false,
+        "description": null,
+        "entities": {"description": {"urls": []}},
+        "favourites_count": 0,
+        "follow_request_sent": null,
+        "followers_count": 10,
+        "friends_count": 0,
+        "geo_enabled": false,
+        "id": id,
+        "is_translator": false,
+        "lang": null,
+        "listed_count": 0,
+        "location": null,
+        "name": screen_name,
+        "profile_background_color": "000000",
+        "profile_background_image_url": null,
+        "profile_background_image_url_https": null,
+        "profile_background_tile": null,
+        "profile_banner_url": null,
+        "profile_image_url": "http://example.com/normal.png",
+        "profile_image_url_https": "https://example.com/normal.png",
+        "profile_link_color": "000000",
+        "profile_sidebar_border_color": "000000",
+        "profile_sidebar_fill_color": "000000",
+        "profile_text_color": "000000",
+        "profile_use_background_image": true,
+        "protected": false,
+        "screen_name": screen_name,
+        "show_all_inline_media": null,
+        "status": null,
+        "statuses_count": 0,
+        "time_zone": null,
+        "url": null,
+        "utc_offset": null,
+        "verified": false,
+        "withheld_in_countries": null,
+        "withheld_scope": null
+    }))
+    .unwrap()
+}
+
+/// Stores a complete snapshot of the account's followers or friends, without any events.
+pub(crate) async fn put_snapshot<P: SnapshotClient>(
+    pool: &P,
+    account: &str,
+    follower: bool,
+    user_ids: &[i64],
+) -> Result<i64> {
+    let id = pool.start_snapshot(account, follower, 0).await?;
+    pool.put_snapshot_members(id, user_ids).await?;
+    pool.finish_snapshot(id, account, &[], 0).await?;
+    Ok(id)
+}
@@ -0,0 +1,176 @@
+use egg_mode::error::Error;
+use std::fmt;
+
+const NO_USER_MATCHES: i32 = 17;
+const PAGE_DOES_NOT_EXIST: i32 = 34;
+const USER_NOT_FOUND: i32 = 50;
+const USER_SUSPENDED: i32 = 63;
+const INVALID_OR_EXPIRED_TOKEN: i32 = 89;
+const CANNOT_FIND_USER: i32 = 108;
+const OVER_CAPACITY: i32 = 130;
+const INTERNAL_ERROR: i32 = 131;
+const UNAUTHORIZED: u16 = 401;
+
+/// An egg-mode error classified by how callers should react to it.
+///
+/// `TwitterClient` returns these wrapped in `anyhow::Error`; use `downcast_ref` to inspect them.
+#[derive(Debug)]
+pub enum TwitterError {
+    RateLimit {
+        reset: i64,
+    },
+    /// Network errors, 5xx responses and "over capacity"; worth retrying.
+    Transient(String),
+    Suspended(String),
+    NotFound(String),
+    InvalidToken(String),
+    Other(String),
+}
+
+impl TwitterError {
+    /// Whether retrying the same request can never succeed, because the target user is gone.
+    pub fn is_permanent(&self) -> bool {
+        matches!(self, TwitterError::Suspended(_) | TwitterError::NotFound(_))
+    }
+}
+
+/// Whether `error` is a `TwitterError` that will not go away by retrying.
+pub fn is_permanent_error(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<TwitterError>()
+        .is_some_and(|e| e.is_permanent())
+}
+
+impl From<Error> for TwitterError {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+        match error {
+            Error::RateLimit(reset) => TwitterError::RateLimit {
+                reset: reset as i64,
+            },
+            Error::NetError(_) | Error::IOError(_) => TwitterError::Transient(message),
+            Error::BadStatus(status) if status.is_server_error() => {
+                TwitterError::Transient(message)
+            }
+            Error::BadStatus(status) if status.as_u16() == UNAUTHORIZED => {
+                TwitterError::InvalidToken(message)
+            }
+            Error::TwitterError(_, errors) => {
+                let codes = errors.errors.iter().map(|e| e.code).collect::<Vec<_>>();
+                if codes.contains(&USER_SUSPENDED) {
+                    TwitterError::Suspended(message)
+                } else if codes.iter().any(|c| {
+                    [
+                        NO_USER_MATCHES,
+                        PAGE_DOES_NOT_EXIST,
+                        USER_NOT_FOUND,
+                        CANNOT_FIND_USER,
+                    ]
+                    .contains(c)
+                }) {
+                    TwitterError::NotFound(message)
+                } else if codes.contains(&INVALID_OR_EXPIRED_TOKEN) {
+                    TwitterError::InvalidToken(message)
+                } else if codes
+                    .iter()
+                    .any(|c| [OVER_CAPACITY, INTERNAL_ERROR].contains(c))
+                {
+                    TwitterError::Transient(message)
+                } else {
+                    TwitterError::Other(message)
+                }
+            }
+            _ => TwitterError::Other(message),
+        }
+    }
+}
+
+impl fmt::Display for TwitterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TwitterError::RateLimit { reset } => write!(f, "Rate limit reached until {}", reset),
+            TwitterError::Transient(message) => write!(f, "Transient error: {}", message),
+            TwitterError::Suspended(message) => write!(f, "User suspended: {}", message),
+            TwitterError::NotFound(message) => write!(f, "Not found: {}", message),
+            TwitterError::InvalidToken(message) => write!(f, "Invalid token: {}", message),
+            TwitterError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for TwitterError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use egg_mode::error::{TwitterErrorCode, TwitterErrors};
+
+    fn twitter_error(codes: &[i32]) -> Error {
+        let errors = codes
+            .iter()
+            .map(|&code| TwitterErrorCode {
+                message: format!("error {}", code),
+                code,
+            })
+            .collect();
+        Error::TwitterError(Default::default(), TwitterErrors { errors })
+    }
+
+    fn kind(error: &TwitterError) -> &'static str {
+        match error {
+            TwitterError::RateLimit { .. } => "rate_limit",
+            TwitterError::Transient(_) => "transient",
+            TwitterError::Suspended(_) => "suspended",
+            TwitterError::NotFound(_) => "not_found",
+            TwitterError::InvalidToken(_) => "invalid_token",
+            TwitterError::Other(_) => "other",
+        }
+    }
+
+    #[test]
+    fn test_from_egg_mode_error() {
+        let io_error = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        let cases = vec![
+            (Error::RateLimit(100), "rate_limit"),
+            (Error::IOError(io_error), "transient"),
+            (Error::BadStatus(StatusCode::BAD_GATEWAY), "transient"),
+            (Error::BadStatus(StatusCode::UNAUTHORIZED), "invalid_token"),
+            (Error::BadStatus(StatusCode::FORBIDDEN), "other"),
+            (twitter_error(&[USER_SUSPENDED]), "suspended"),
+            (twitter_error(&[NO_USER_MATCHES]), "not_found"),
+            (twitter_error(&[PAGE_DOES_NOT_EXIST]), "not_found"),
+            (twitter_error(&[USER_NOT_FOUND]), "not_found"),
+            (twitter_error(&[CANNOT_FIND_USER]), "not_found"),
+            (twitter_error(&[INVALID_OR_EXPIRED_TOKEN]), "invalid_token"),
+            (twitter_error(&[OVER_CAPACITY]), "transient"),
+            (twitter_error(&[INTERNAL_ERROR]), "transient"),
+            (twitter_error(&[161]), "other"),
+            // A suspension wins over any other code in the same response.
+            (twitter_error(&[OVER_CAPACITY, USER_SUSPENDED]), "suspended"),
+            (Error::BadUrl, "other"),
+        ];
+        for (error, expected) in cases {
+            let message = error.to_string();
+            assert_eq!(kind(&TwitterError::from(error)), expected, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_reset() {
+        match TwitterError::from(Error::RateLimit(100)) {
+            TwitterError::RateLimit { reset } => assert_eq!(reset, 100),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_is_permanent_error() {
+        let permanent = |e: TwitterError| is_permanent_error(&e.into());
+        assert!(permanent(TwitterError::Suspended(String::new())));
+        assert!(permanent(TwitterError::NotFound(String::new())));
+        assert!(!permanent(TwitterError::Transient(String::new())));
+        assert!(!permanent(TwitterError::RateLimit { reset: 0 }));
+        assert!(!is_permanent_error(&anyhow::anyhow!("error")));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::twitter::{Priority, TwitterApi, TwitterError};
+use anyhow::Result;
+use async_trait::async_trait;
+use egg_mode::user::{Connection, RelationLookup, TwitterUser};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+/// In-memory `TwitterApi` backed by a configurable social graph around one account.
+#[derive(Clone)]
+pub struct FakeTwitterClient {
+    account: String,
+    screen_name: String,
+    page_size: usize,
+    graph: Arc<Mutex<SocialGraph>>,
+}
+
+#[derive(Default)]
+struct SocialGraph {
+    users: BTreeMap<u64, TwitterUser>,
+    friends: BTreeSet<u64>,
+    followers: BTreeSet<u64>,
+    pending: BTreeSet<u64>,
+    suspended: BTreeSet<u64>,
+}
+
+impl FakeTwitterClient {
+    pub fn new<S: Into<String>>(user_id: u64, screen_name: S) -> Self {
+        Self {
+            account: user_id.to_string(),
+            screen_name: screen_name.into(),
+            page_size: 5000,
+            graph: Arc::new(Mutex::new(SocialGraph::default())),
+        }
+    }
+
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    pub fn add_user(&self, user: TwitterUser) {
+        self.graph.lock().unwrap().users.insert(user.id, user);
+    }
+    pub fn add_friend(&self, user_id: u64) {
+        self.graph.lock().unwrap().friends.insert(user_id);
+    }
+    pub fn add_follower(&self, user_id: u64) {
+        self.graph.lock().unwrap().followers.insert(user_id);
+    }
+    pub fn add_pending(&self, user_id: u64) {
+        self.graph.lock().unwrap().pending.insert(user_id);
+    }
+    /// Hides the user from lookups, as Twitter does with suspended accounts.
+    pub fn suspend_user(&self, user_id: u64) {
+        self.graph.lock().unwrap().suspended.insert(user_id);
+    }
+    pub fn remove_follower(&self, user_id: u64) {
+        self.graph.lock().unwrap().followers.remove(&user_id);
+    }
+
+    pub fn friends(&self) -> Vec<u64> {
+        self.graph.lock().unwrap().friends.iter().copied().collect()
+    }
+    pub fn followers(&self) -> Vec<u64> {
+        self.graph
+            .lock()
+            .unwrap()
+            .followers
+            .iter()
+            .copied()
+            .collect()
+    }
+    pub fn pending(&self) -> Vec<u64> {
+        self.graph.lock().unwrap().pending.iter().copied().collect()
+    }
+}
+
+#[async_trait(?Send)]
+impl TwitterApi for FakeTwitterClient {
+    fn account(&self) -> &str {
+        &self.account
+    }
+
+    fn screen_name(&self) -> &str {
+        &self.screen_name
+    }
+
+    async fn fetch_ids(&self, cursor: i64, follower: bool) -> Result<(Vec<u64>, i64)> {
+        let graph = self.graph.lock().unwrap();
+        let ids = if follower {
+            &graph.followers
+        } else {
+            &graph.friends
+        };
+
+        // Cursors are offsets into the id list; -1 is the first page and 0 means no more pages.
+        let offset = if cursor < 0 { 0 } else { cursor as usize };
+        let page = ids
+            .iter()
+            .skip(offset)
+            .take(self.page_size)
+            .copied()
+            .collect::<Vec<_>>();
+        let next = offset + page.len();
+        let next_cursor = if next < ids.len() { next as i64 } else { 0 };
+        Ok((page, next_cursor))
+    }
+
+    async fn get_relations(
+        &self,
+        user_ids: &[u64],
+        _priority: Priority,
+    ) -> Result<Vec<RelationLookup>> {
+        let graph = self.graph.lock().unwrap();
+        let relations = user_ids
+            .iter()
+            .filter_map(|id| graph.users.get(id))
+            .map(|user| {
+                let mut connections = vec![];
+                if graph.friends.contains(&user.id) {
+                    connections.push(Connection::Following);
+                }
+                if graph.pending.contains(&user.id) {
+                    connections.push(Connection::FollowingRequested);
+                }
+                if graph.followers.contains(&user.id) {
+                    connections.push(Connection::FollowedBy);
+                }
+                if connections.is_empty() {
+                    connections.push(Connection::None);
+                }
+                RelationLookup {
+                    name: user.name.clone(),
+                    screen_name: user.screen_name.clone(),
+                    id: user.id,
+                    connections,
+                }
+            })
+            .collect();
+        Ok(relations)
+    }
+
+    async fn get_user_data(
+        &self,
+        user_ids: &[u64],
+        _priority: Priority,
+    ) -> Result<Vec<TwitterUser>> {
+        let graph = self.graph.lock().unwrap();
+        let users = user_ids
+            .iter()
+            .filter(|id| !graph.suspended.contains(id))
+            .filter_map(|id| graph.users.get(id))
+            .cloned()
+            .collect();
+        Ok(users)
+    }
+
+    async fn get_user(&self, user_id: u64, _priority: Priority) -> Result<TwitterUser> {
+        let graph = self.graph.lock().unwrap();
+        if graph.suspended.contains(&user_id) {
+            return Err(
+                TwitterError::Suspended(format!("User has been suspended: {}", user_id)).into(),
+            );
+        }
+        let user = graph
+            .users
+            .get(&user_id)
+            .cloned()
+            .ok_or_else(|| TwitterError::NotFound(format!("User not found: {}", user_id)))?;
+        Ok(user)
+    }
+
+    async fn follow(&self, user_id: u64) -> Result<TwitterUser> {
+        let mut graph = self.graph.lock().unwrap();
+        let user = graph
+            .users
+            .get(&user_id)
+            .cloned()
+            .ok_or_else(|| TwitterError::NotFound(format!("User not found: {}", user_id)))?;
+        if user.protected {
+            graph.pending.insert(user_id);
+        } else {
+            graph.friends.insert(user_id);
+        }
+        Ok(user)
+    }
+
+    async fn unfollow(&self, user_id: u64) -> Result<TwitterUser> {
+        let mut graph = self.graph.lock().unwrap();
+        let user = graph
+            .users
+            .get(&user_id)
+            .cloned()
+            .ok_or_else(|| TwitterError::NotFound(format!("User not found: {}", user_id)))?;
+        graph.friends.remove(&user_id);
+        graph.pending.remove(&user_id);
+        Ok(user)
+    }
+}
@@ -0,0 +1,233 @@
+use actix::clock::sleep;
+use anyhow::Result;
+use async_trait::async_trait;
+use egg_mode::user::{
+    follow, followers_ids, friends_ids, lookup, relation_lookup, show, unfollow, Connection,
+    RelationLookup, TwitterUser,
+};
+use egg_mode::Token;
+use std::future::Future;
+
+mod error;
+mod fake;
+mod rate_limit;
+mod retry;
+pub use error::{is_permanent_error, TwitterError};
+pub use fake::FakeTwitterClient;
+pub use rate_limit::{Priority, RateLimitConfig, RateLimiter};
+pub use retry::RetryConfig;
+
+#[async_trait(?Send)]
+pub trait TwitterApi {
+    /// Key of the account in storage and in the HTTP API: its user id, which unlike the screen
+    /// name never changes.
+    fn account(&self) -> &str;
+    /// For display only.
+    fn screen_name(&self) -> &str;
+
+    /// Fetches a page of the account's own followers or friends.
+    async fn fetch_ids(&self, cursor: i64, follower: bool) -> Result<(Vec<u64>, i64)>;
+    async fn get_relations(
+        &self,
+        user_ids: &[u64],
+        priority: Priority,
+    ) -> Result<Vec<RelationLookup>>;
+    async fn get_user_data(&self, user_ids: &[u64], priority: Priority)
+        -> Result<Vec<TwitterUser>>;
+    /// Fetches one user, failing with `TwitterError::Suspended` or `TwitterError::NotFound` for
+    /// accounts that `get_user_data` leaves out.
+    async fn get_user(&self, user_id: u64, priority: Priority) -> Result<TwitterUser>;
+
+    async fn follow(&self, user_id: u64) -> Result<TwitterUser>;
+    async fn unfollow(&self, user_id: u64) -> Result<TwitterUser>;
+}
+
+#[derive(Clone)]
+pub struct TwitterClient {
+    pub token: Token,
+    pub user_id: u64,
+    /// `user_id` as a string, returned by `account`.
+    pub account: String,
+    pub screen_name: String,
+    pub rate_limiter: RateLimiter,
+    pub retry: RetryConfig,
+}
+
+#[async_trait(?Send)]
+impl TwitterApi for TwitterClient {
+    fn account(&self) -> &str {
+        &self.account
+    }
+
+    fn screen_name(&self) -> &str {
+        &self.screen_name
+    }
+
+    async fn fetch_ids(&self, cursor: i64, follower: bool) -> Result<(Vec<u64>, i64)> {
+        let (c, api_name) = if follower {
+            (followers_ids(self.user_id, &self.token), "followers_ids")
+        } else {
+            (friends_ids(self.user_id, &self.token), "friends_ids")
+        };
+        let mut c = c.with_page_size(5000);
+        c.next_cursor = cursor;
+        let response = self
+            .wait_and_call(|| c.call(), Priority::Background, api_name)
+            .await?
+            .response;
+        Ok((response.ids, response.next_cursor))
+    }
+    async fn get_relations(
+        &self,
+        user_ids: &[u64],
+        priority: Priority,
+    ) -> Result<Vec<RelationLookup>> {
+        self.wait_and_call(
+            || relation_lookup(user_ids.to_vec(), &self.token),
+            priority,
+            "relation_lookup",
+        )
+        .await
+        .map(|response| response.response)
+    }
+
+    async fn get_user_data(
+        &self,
+        user_ids: &[u64],
+        priority: Priority,
+    ) -> Result<Vec<TwitterUser>> {
+        log::info!("Fetching data of {} users", user_ids.len());
+        self.wait_and_call(
+            || lookup(user_ids.to_vec(), &self.token),
+            priority,
+            "lookup",
+        )
+        .await
+        .map(|response| response.response)
+    }
+
+    async fn get_user(&self, user_id: u64, priority: Priority) -> Result<TwitterUser> {
+        self.wait_and_call(|| show(user_id, &self.token), priority, "show")
+            .await
+            .map(|response| response.response)
+    }
+
+    async fn follow(&self, user_id: u64) -> Result<TwitterUser> {
+        let response = self
+            .call_with_retry(|| follow(user_id, false, &self.token), "follow")
+            .await?;
+        Ok(response.response)
+    }
+
+    async fn unfollow(&self, user_id: u64) -> Result<TwitterUser> {
+        let response = self
+            .call_with_retry(|| unfollow(user_id, &self.token), "unfollow")
+            .await?;
+        Ok(response.response)
+    }
+}
+
+impl TwitterClient {
+    async fn wait_and_call<F, T, Fut>(
+        &self,
+        f: F,
+        priority: Priority,
+        api_name: &'static str,
+    ) -> Result<egg_mode::Response<T>>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = egg_mode::error::Result<egg_mode::Response<T>>>,
+    {
+        let mut attempt = 0;
+        loop {
+            // Every attempt, including retries of transient errors, takes a slot of the window.
+            self.rate_limiter.acquire(api_name, priority).await?;
+            match f().await.map_err(TwitterError::from) {
+                Ok(response) => {
+                    self.rate_limiter
+                        .update(api_name, &response.rate_limit_status);
+                    return Ok(response);
+                }
+                Err(TwitterError::RateLimit { reset }) => {
+                    // Another client sharing the token may have used up the window.
+                    self.rate_limiter.exhaust(api_name, reset);
+                    if priority == Priority::Interactive {
+                        log::error!("Rate Limit Exceeded: {}", api_name);
+                        return Err(TwitterError::RateLimit { reset }.into());
+                    }
+                }
+                Err(TwitterError::Transient(message)) if attempt < self.retry.max_retries => {
+                    self.back_off(api_name, &message, attempt).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+
+    /// Calls `f`, retrying transient errors with exponential backoff. Only for endpoints the rate
+    /// limiter does not track.
+    async fn call_with_retry<F, T, Fut>(&self, f: F, api_name: &str) -> Result<T, TwitterError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = egg_mode::error::Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match f().await.map_err(TwitterError::from) {
+                Err(TwitterError::Transient(message)) if attempt < self.retry.max_retries => {
+                    self.back_off(api_name, &message, attempt).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn back_off(&self, api_name: &str, message: &str, attempt: u32) {
+        let delay = self.retry.delay(attempt);
+        log::warn!(
+            "{} failed: {}. Retrying in {} ms ...",
+            api_name,
+            message,
+            delay.as_millis()
+        );
+        sleep(delay).await;
+    }
+}
+
+pub(crate) trait RelationLookupExt {
+    fn is_friend(&self) -> bool;
+    fn is_follower(&self) -> bool;
+    fn is_pending(&self) -> bool;
+}
+
+impl RelationLookupExt for RelationLookup {
+    fn is_friend(&self) -> bool {
+        for connection in self.connections.iter() {
+            if let Connection::Following = connection {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn is_follower(&self) -> bool {
+        for connection in self.connections.iter() {
+            if let Connection::FollowedBy = connection {
+                return true;
+            }
+        }
+        false
+    }
+    fn is_pending(&self) -> bool {
+        for connection in self.connections.iter() {
+            if let Connection::FollowingRequested = connection {
+                return true;
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,229 @@
+use crate::current_time_duration;
+use crate::twitter::TwitterError;
+use actix::clock::sleep;
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    /// Requests made on behalf of a user waiting on the HTTP API.
+    Interactive,
+    /// Requests made by the synchronizers and workers.
+    Background,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Calls per window that background requests leave for interactive ones.
+    pub interactive_reserve: i32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            interactive_reserve: 2,
+        }
+    }
+}
+
+/// Per-endpoint rate budget shared by every clone of a client.
+///
+/// The budget is kept in sync with the `x-rate-limit-remaining`/`x-rate-limit-reset` headers of
+/// each response. Background callers queue up per endpoint in FIFO order and wait for the
+/// window to reset instead of spending the calls reserved for interactive requests.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    endpoints: Arc<Mutex<BTreeMap<&'static str, Arc<Endpoint>>>>,
+}
+
+#[derive(Default)]
+struct Endpoint {
+    budget: Mutex<Budget>,
+    queue: tokio::sync::Mutex<()>,
+}
+
+#[derive(Default)]
+struct Budget {
+    remaining: Option<i32>,
+    reset: i64,
+}
+
+impl Budget {
+    fn refresh(&mut self, now: i64) {
+        if self.remaining.is_some() && self.reset <= now {
+            self.remaining = None;
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            endpoints: Default::default(),
+        }
+    }
+
+    fn endpoint(&self, name: &'static str) -> Arc<Endpoint> {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        endpoints.entry(name).or_default().clone()
+    }
+
+    /// Waits until a call to `name` fits in the budget and reserves it.
+    pub(crate) async fn acquire(&self, name: &'static str, priority: Priority) -> Result<()> {
+        let endpoint = self.endpoint(name);
+        match priority {
+            Priority::Interactive => {
+                let now = current_time_duration().as_secs() as i64;
+                let mut budget = endpoint.budget.lock().unwrap();
+                budget.refresh(now);
+                match budget.remaining {
+                    Some(remaining) if remaining <= 0 => Err(TwitterError::RateLimit {
+                        reset: budget.reset,
+                    }
+                    .into()),
+                    Some(remaining) => {
+                        budget.remaining = Some(remaining - 1);
+                        Ok(())
+                    }
+                    None => Ok(()),
+                }
+            }
+            Priority::Background => {
+                let _turn = endpoint.queue.lock().await;
+                loop {
+                    let now = current_time_duration().as_secs() as i64;
+                    let wait = {
+                        let mut budget = endpoint.budget.lock().unwrap();
+                        budget.refresh(now);
+                        match budget.remaining {
+                            Some(remaining) if remaining <= self.config.interactive_reserve => {
+                                Some(budget.reset - now + 1)
+                            }
+                            Some(remaining) => {
+                                budget.remaining = Some(remaining - 1);
+                                None
+                            }
+                            None => None,
+                        }
+                    };
+                    match wait {
+                        Some(seconds) => {
+                            log::info!(
+                                "Rate budget of {} is used up: Sleeping {} seconds",
+                                name,
+                                seconds
+                            );
+                            sleep(Duration::from_secs(seconds as u64)).await;
+                        }
+                        None => return Ok(()),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Records the rate limit status returned with a response.
+    pub(crate) fn update(&self, name: &'static str, status: &egg_mode::RateLimit) {
+        if status.remaining < 0 {
+            // The response did not carry rate limit headers.
+            return;
+        }
+        let endpoint = self.endpoint(name);
+        let mut budget = endpoint.budget.lock().unwrap();
+        budget.remaining = Some(status.remaining);
+        budget.reset = status.reset as i64;
+    }
+
+    /// Marks the window of `name` as exhausted until `reset`.
+    pub(crate) fn exhaust(&self, name: &'static str, reset: i64) {
+        let endpoint = self.endpoint(name);
+        let mut budget = endpoint.budget.lock().unwrap();
+        budget.remaining = Some(0);
+        budget.reset = reset;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix::clock::timeout;
+
+    const NAME: &str = "test";
+
+    fn limiter(remaining: i32, reset: i64) -> RateLimiter {
+        let limiter = RateLimiter::default();
+        let status = egg_mode::RateLimit {
+            limit: 15,
+            remaining,
+            reset: reset as i32,
+        };
+        limiter.update(NAME, &status);
+        limiter
+    }
+
+    fn now() -> i64 {
+        current_time_duration().as_secs() as i64
+    }
+
+    #[actix::test]
+    async fn test_interactive_uses_up_budget() {
+        let reset = now() + 900;
+        let limiter = limiter(2, reset);
+        for _ in 0..2 {
+            limiter.acquire(NAME, Priority::Interactive).await.unwrap();
+        }
+        let error = limiter
+            .acquire(NAME, Priority::Interactive)
+            .await
+            .unwrap_err();
+        match error.downcast_ref::<TwitterError>() {
+            Some(TwitterError::RateLimit { reset: r }) => assert_eq!(*r, reset),
+            _ => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[actix::test]
+    async fn test_budget_resets() {
+        let limiter = limiter(0, now() - 1);
+        limiter.acquire(NAME, Priority::Interactive).await.unwrap();
+
+        limiter.exhaust(NAME, now() - 1);
+        limiter.acquire(NAME, Priority::Background).await.unwrap();
+
+        // Responses without rate limit headers leave the budget alone.
+        limiter.exhaust(NAME, now() + 900);
+        let status = egg_mode::RateLimit {
+            limit: -1,
+            remaining: -1,
+            reset: -1,
+        };
+        limiter.update(NAME, &status);
+        assert!(limiter.acquire(NAME, Priority::Interactive).await.is_err());
+    }
+
+    #[actix::test]
+    async fn test_background_leaves_reserve() {
+        let limiter = limiter(3, now() + 900);
+        limiter.acquire(NAME, Priority::Background).await.unwrap();
+
+        // The remaining two calls are reserved for interactive requests.
+        let waiting = limiter.acquire(NAME, Priority::Background);
+        assert!(timeout(Duration::from_millis(50), waiting).await.is_err());
+        for _ in 0..2 {
+            limiter.acquire(NAME, Priority::Interactive).await.unwrap();
+        }
+        assert!(limiter.acquire(NAME, Priority::Interactive).await.is_err());
+
+        // Other endpoints have their own budget.
+        limiter
+            .acquire("other", Priority::Background)
+            .await
+            .unwrap();
+    }
+}
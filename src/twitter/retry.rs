@@ -0,0 +1,77 @@
+use rand::prelude::*;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Exponential backoff for transient Twitter errors.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct RetryConfig {
+    pub max_retries: u32,
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub multiplier: f64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_delay_ms: 1000,
+            max_delay_ms: 60_000,
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// The delay before retry number `attempt` (starting at 0), with up to 50% jitter.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.initial_delay_ms as f64 * self.multiplier.powi(attempt as i32);
+        let delay = delay.min(self.max_delay_ms as f64);
+        let jitter = thread_rng().gen_range(0.5..=1.0);
+        Duration::from_millis((delay * jitter) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let config = RetryConfig {
+            max_retries: 5,
+            initial_delay_ms: 100,
+            max_delay_ms: 1000,
+            multiplier: 2.0,
+        };
+        let cases = [
+            (0, 100),
+            (1, 200),
+            (2, 400),
+            (3, 800),
+            (4, 1000),
+            (10, 1000),
+        ];
+        for (attempt, expected_ms) in cases {
+            for _ in 0..100 {
+                let delay = config.delay(attempt).as_millis() as u64;
+                assert!(
+                    expected_ms / 2 <= delay && delay <= expected_ms,
+                    "attempt {}: {}ms",
+                    attempt,
+                    delay
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_delay_jitter() {
+        let config = RetryConfig::default();
+        let delays = (0..100)
+            .map(|_| config.delay(3))
+            .collect::<std::collections::BTreeSet<_>>();
+        assert!(delays.len() > 1);
+    }
+}
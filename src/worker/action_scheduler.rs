@@ -0,0 +1,526 @@
+use crate::action::{
+    perform_action, ActionKind, ActionRefused, Actor, API_CALL_RESULTS, DRY_RUN_QUOTA_RESULTS,
+};
+use crate::current_time_duration;
+use crate::sql::{
+    ActionLogClient, ActionQueueClient, AllowlistClient, BlocklistClient, PgPoolExt, QueueStatus,
+    QueuedAction, UserStatus, UserStatusClient,
+};
+use crate::twitter::{is_permanent_error, Priority, RelationLookupExt, TwitterApi};
+use actix::clock::sleep;
+use actix_web::rt::task::JoinHandle;
+use anyhow::Result;
+use rand::prelude::*;
+use serde::Deserialize;
+use std::time::Duration;
+
+const SECONDS_PER_HOUR: i64 = 3600;
+const SECONDS_PER_DAY: i64 = SECONDS_PER_HOUR * 24;
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct ActionQuota {
+    pub per_day: i64,
+    pub per_hour: i64,
+}
+
+/// Hours of the day, in the given UTC offset, during which no action is performed. The range
+/// may wrap around midnight, e.g. from 23 to 7.
+#[derive(Deserialize, Clone, Copy)]
+pub struct QuietHours {
+    pub start_hour: i64,
+    pub end_hour: i64,
+    #[serde(default)]
+    pub utc_offset_hours: i64,
+}
+
+impl QuietHours {
+    fn contains(&self, now: i64) -> bool {
+        let hour = (now + self.utc_offset_hours * SECONDS_PER_HOUR).rem_euclid(SECONDS_PER_DAY)
+            / SECONDS_PER_HOUR;
+        if self.start_hour <= self.end_hour {
+            self.start_hour <= hour && hour < self.end_hour
+        } else {
+            self.start_hour <= hour || hour < self.end_hour
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct ActionSchedulerConfig {
+    pub follow: ActionQuota,
+    pub unfollow: ActionQuota,
+    /// Each action is followed by a random pause between these bounds.
+    pub min_interval_secs: u64,
+    pub max_interval_secs: u64,
+    pub quiet_hours: Option<QuietHours>,
+    /// A failed action is given up after this many attempts.
+    pub max_attempts: i32,
+    /// Delay before the second attempt, doubled for each further one.
+    pub retry_delay_secs: i64,
+}
+
+impl Default for ActionSchedulerConfig {
+    fn default() -> Self {
+        Self {
+            follow: ActionQuota {
+                per_day: 400,
+                per_hour: 30,
+            },
+            unfollow: ActionQuota {
+                per_day: 400,
+                per_hour: 30,
+            },
+            min_interval_secs: 60,
+            max_interval_secs: 180,
+            quiet_hours: None,
+            max_attempts: 5,
+            retry_delay_secs: 600,
+        }
+    }
+}
+
+impl ActionSchedulerConfig {
+    fn quota(&self, kind: ActionKind) -> ActionQuota {
+        match kind {
+            ActionKind::Follow => self.follow,
+            ActionKind::Unfollow => self.unfollow,
+        }
+    }
+
+    fn retry_delay(&self, attempts: i32) -> i64 {
+        self.retry_delay_secs << (attempts - 1).clamp(0, 16)
+    }
+}
+
+/// Performs the follows and unfollows queued by the other workers of an account, one at a time
+/// and within the configured quotas.
+pub struct ActionScheduler<P, T> {
+    pub pool: P,
+    pub client: T,
+    pub config: ActionSchedulerConfig,
+    pub dry_run: bool,
+}
+
+impl<P, T> ActionScheduler<P, T>
+where
+    P: PgPoolExt
+        + ActionQueueClient
+        + ActionLogClient
+        + BlocklistClient
+        + AllowlistClient
+        + UserStatusClient
+        + 'static,
+    T: TwitterApi + 'static,
+{
+    pub fn start(self) -> JoinHandle<()> {
+        actix::spawn(async move {
+            let mut rng = thread_rng();
+            // Actions claimed before a restart were interrupted and are attempted again.
+            let now = current_time_duration().as_secs() as i64;
+            match self
+                .pool
+                .reset_running_actions(self.client.account(), now)
+                .await
+            {
+                Ok(0) => {}
+                Ok(count) => log::info!("Resuming {} interrupted actions", count),
+                Err(e) => log::error!("{:?}", e),
+            }
+            loop {
+                let duration = match self.perform_next().await {
+                    Ok(true) => {
+                        let (min, max) = (
+                            self.config.min_interval_secs,
+                            self.config
+                                .max_interval_secs
+                                .max(self.config.min_interval_secs),
+                        );
+                        Duration::from_secs(rng.gen_range(min..=max))
+                    }
+                    Ok(false) => Duration::from_secs(60),
+                    Err(e) => {
+                        log::error!("{:?}", e);
+                        Duration::from_secs(60)
+                    }
+                };
+                sleep(duration).await;
+            }
+        })
+    }
+
+    /// Performs the oldest due action whose quota is not used up. Returns `false` if there was
+    /// nothing to do.
+    pub(crate) async fn perform_next(&self) -> Result<bool> {
+        let now = current_time_duration().as_secs() as i64;
+        if let Some(quiet_hours) = self.config.quiet_hours {
+            if quiet_hours.contains(now) {
+                return Ok(false);
+            }
+        }
+
+        let account = self.client.account();
+        let results = if self.dry_run {
+            DRY_RUN_QUOTA_RESULTS
+        } else {
+            API_CALL_RESULTS
+        };
+        let mut actions = vec![];
+        for kind in [ActionKind::Follow, ActionKind::Unfollow] {
+            let quota = self.config.quota(kind);
+            let daily = self
+                .pool
+                .count_actions(account, kind.as_str(), results, now - SECONDS_PER_DAY)
+                .await?;
+            let hourly = self
+                .pool
+                .count_actions(account, kind.as_str(), results, now - SECONDS_PER_HOUR)
+                .await?;
+            if daily < quota.per_day && hourly < quota.per_hour {
+                actions.push(kind.as_str());
+            }
+        }
+        if actions.is_empty() {
+            return Ok(false);
+        }
+
+        let (queued, result) = loop {
+            let queued = match self
+                .pool
+                .claim_queued_action(account, &actions, now)
+                .await?
+            {
+                Some(queued) => queued,
+                None => return Ok(false),
+            };
+            match self.obsolete_reason(&queued).await {
+                Ok(Some(reason)) => {
+                    log::info!("Cancelling action {}: {}", queued.id, reason);
+                    let now = current_time_duration().as_secs() as i64;
+                    self.pool
+                        .update_queued_action(
+                            queued.id,
+                            QueueStatus::Cancelled,
+                            Some(&reason),
+                            queued.next_attempt_at,
+                            now,
+                        )
+                        .await?;
+                }
+                Ok(None) => {
+                    let result = self.perform(&queued).await;
+                    break (queued, result);
+                }
+                Err(e) => break (queued, Err(e)),
+            }
+        };
+        let (status, error, next_attempt_at) = match result {
+            Ok(()) => (QueueStatus::Done, None, queued.next_attempt_at),
+            Err(e) if is_permanent_error(&e) || e.is::<ActionRefused>() => {
+                log::warn!("Giving up action {}: {}", queued.id, e);
+                (
+                    QueueStatus::Failed,
+                    Some(e.to_string()),
+                    queued.next_attempt_at,
+                )
+            }
+            Err(e) if queued.attempts >= self.config.max_attempts => {
+                log::error!(
+                    "Action {} failed {} times: {:?}",
+                    queued.id,
+                    queued.attempts,
+                    e
+                );
+                (
+                    QueueStatus::Failed,
+                    Some(e.to_string()),
+                    queued.next_attempt_at,
+                )
+            }
+            Err(e) => {
+                let next_attempt_at = now + self.config.retry_delay(queued.attempts);
+                log::warn!(
+                    "Action {} failed, retrying at {}: {:?}",
+                    queued.id,
+                    next_attempt_at,
+                    e
+                );
+                (QueueStatus::Pending, Some(e.to_string()), next_attempt_at)
+            }
+        };
+        let now = current_time_duration().as_secs() as i64;
+        self.pool
+            .update_queued_action(queued.id, status, error.as_deref(), next_attempt_at, now)
+            .await?;
+        Ok(true)
+    }
+
+    /// Why an action decided by a worker is no longer justified by the live relation, if it is
+    /// not. The relation may have changed while the action was waiting in the queue.
+    async fn obsolete_reason(&self, queued: &QueuedAction) -> Result<Option<String>> {
+        let actor = Actor::parse(&queued.actor);
+        if !matches!(
+            actor,
+            Some(Actor::FollowBackWorker | Actor::InvalidUserRemover)
+        ) {
+            return Ok(None);
+        }
+        let kind = ActionKind::parse(&queued.action);
+        if matches!(kind, Some(ActionKind::Unfollow)) {
+            match self.pool.get_user_status(queued.user_id).await? {
+                Some(UserStatus::Active) | None => {}
+                // Suspensions can be lifted, so make sure the account is still gone.
+                Some(_) => {
+                    return match self
+                        .client
+                        .get_user(queued.user_id as u64, Priority::Background)
+                        .await
+                    {
+                        Ok(user) => {
+                            self.pool.put_user_info(&user).await?;
+                            Ok(Some("the account is active again".to_string()))
+                        }
+                        Err(e) if is_permanent_error(&e) => Ok(None),
+                        Err(e) => Err(e),
+                    };
+                }
+            }
+        }
+        let relations = self
+            .client
+            .get_relations(&[queued.user_id as u64], Priority::Background)
+            .await?;
+        let relation = match relations.first() {
+            Some(relation) => relation,
+            // Suspended and deleted accounts are left out of relation lookups.
+            None => return Ok(None),
+        };
+        let reason = match kind {
+            Some(ActionKind::Follow) if relation.is_friend() || relation.is_pending() => {
+                Some("already following the user")
+            }
+            Some(ActionKind::Follow) if !relation.is_follower() => {
+                Some("the user no longer follows the account")
+            }
+            Some(ActionKind::Unfollow) if !relation.is_friend() => Some("not following the user"),
+            Some(ActionKind::Unfollow) if relation.is_follower() => {
+                Some("the user follows the account now")
+            }
+            _ => None,
+        };
+        Ok(reason.map(|reason| reason.to_string()))
+    }
+
+    async fn perform(&self, queued: &QueuedAction) -> Result<()> {
+        let kind = ActionKind::parse(&queued.action)
+            .ok_or_else(|| ActionRefused(format!("Unknown action: {}", queued.action)))?;
+        let actor = Actor::parse(&queued.actor)
+            .ok_or_else(|| ActionRefused(format!("Unknown actor: {}", queued.actor)))?;
+        log::info!(
+            "Performing {} of {} ({}, attempt {})",
+            kind.as_str(),
+            queued.user_id,
+            queued.reason,
+            queued.attempts
+        );
+        match perform_action(
+            &self.pool,
+            &self.client,
+            kind,
+            queued.user_id as u64,
+            actor,
+            &queued.reason,
+            self.dry_run,
+        )
+        .await?
+        {
+            Some(user) => log::info!("Performed {} of @{}", kind.as_str(), user.screen_name),
+            None => log::info!("Would {} {} (dry run)", kind.as_str(), queued.user_id),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::enqueue_action;
+    use crate::sql::{ActionFilter, InMemoryPool};
+    use crate::test_utils::user;
+    use crate::twitter::FakeTwitterClient;
+
+    fn scheduler(
+        pool: &InMemoryPool,
+        client: &FakeTwitterClient,
+        dry_run: bool,
+    ) -> ActionScheduler<InMemoryPool, FakeTwitterClient> {
+        ActionScheduler {
+            pool: pool.clone(),
+            client: client.clone(),
+            config: ActionSchedulerConfig::default(),
+            dry_run,
+        }
+    }
+
+    async fn enqueue(pool: &InMemoryPool, kind: ActionKind, user_id: u64, actor: Actor) -> i64 {
+        enqueue_action(pool, "1", kind, user_id, actor, "test")
+            .await
+            .unwrap()
+            .id
+    }
+
+    async fn queued(pool: &InMemoryPool, id: i64) -> QueuedAction {
+        pool.get_queued_actions_by_ids(&[id])
+            .await
+            .unwrap()
+            .pop()
+            .unwrap()
+    }
+
+    fn client_with_users(ids: &[u64]) -> FakeTwitterClient {
+        let client = FakeTwitterClient::new(1, "me");
+        for &id in ids {
+            client.add_user(user(id, "alice"));
+        }
+        client
+    }
+
+    #[actix::test]
+    async fn test_perform_next() {
+        let pool = InMemoryPool::default();
+        let client = client_with_users(&[10, 11]);
+        client.add_follower(10);
+        client.add_friend(11);
+        let follow = enqueue(&pool, ActionKind::Follow, 10, Actor::FollowBackWorker).await;
+        let unfollow = enqueue(&pool, ActionKind::Unfollow, 11, Actor::InvalidUserRemover).await;
+
+        let scheduler = scheduler(&pool, &client, false);
+        assert!(scheduler.perform_next().await.unwrap());
+        assert!(scheduler.perform_next().await.unwrap());
+        assert!(!scheduler.perform_next().await.unwrap());
+        assert_eq!(client.friends(), vec![10]);
+        for id in [follow, unfollow] {
+            let queued = queued(&pool, id).await;
+            assert_eq!(queued.status, QueueStatus::Done);
+            assert_eq!(queued.attempts, 1);
+        }
+        let actions = pool
+            .get_actions("1", &ActionFilter::default(), 10)
+            .await
+            .unwrap();
+        assert_eq!(actions.len(), 2);
+        assert!(actions.iter().all(|action| action.result == "succeeded"));
+    }
+
+    #[actix::test]
+    async fn test_perform_next_dry_run() {
+        let pool = InMemoryPool::default();
+        let client = client_with_users(&[10]);
+        client.add_follower(10);
+        let id = enqueue(&pool, ActionKind::Follow, 10, Actor::FollowBackWorker).await;
+
+        assert!(scheduler(&pool, &client, true)
+            .perform_next()
+            .await
+            .unwrap());
+        assert!(client.friends().is_empty());
+        assert_eq!(queued(&pool, id).await.status, QueueStatus::Done);
+    }
+
+    #[actix::test]
+    async fn test_perform_next_cancels_obsolete_actions() {
+        let pool = InMemoryPool::default();
+        let client = client_with_users(&[10, 11, 12]);
+        client.add_friend(11);
+        client.add_follower(11);
+        client.add_friend(12);
+        client.add_follower(12);
+        let follow = enqueue(&pool, ActionKind::Follow, 10, Actor::FollowBackWorker).await;
+        let unfollow = enqueue(&pool, ActionKind::Unfollow, 11, Actor::InvalidUserRemover).await;
+        // Manual removals are performed regardless of the relation.
+        let removal = enqueue(&pool, ActionKind::Unfollow, 12, Actor::RemoveUserHandler).await;
+
+        let scheduler = scheduler(&pool, &client, false);
+        assert!(scheduler.perform_next().await.unwrap());
+        assert!(!scheduler.perform_next().await.unwrap());
+        assert_eq!(client.friends(), vec![11]);
+        let cancelled = [
+            (follow, "the user no longer follows the account"),
+            (unfollow, "the user follows the account now"),
+        ];
+        for (id, reason) in cancelled {
+            let queued = queued(&pool, id).await;
+            assert_eq!(queued.status, QueueStatus::Cancelled);
+            assert_eq!(queued.last_error.as_deref(), Some(reason));
+        }
+        assert_eq!(queued(&pool, removal).await.status, QueueStatus::Done);
+    }
+
+    #[actix::test]
+    async fn test_perform_next_rechecks_dead_users() {
+        let pool = InMemoryPool::default();
+        let client = client_with_users(&[10, 11]);
+        client.add_friend(10);
+        client.add_friend(11);
+        client.suspend_user(11);
+        for id in [10, 11] {
+            pool.put_user_status(id, UserStatus::Suspended)
+                .await
+                .unwrap();
+        }
+        let revived = enqueue(&pool, ActionKind::Unfollow, 10, Actor::InvalidUserRemover).await;
+        let suspended = enqueue(&pool, ActionKind::Unfollow, 11, Actor::InvalidUserRemover).await;
+
+        let scheduler = scheduler(&pool, &client, false);
+        assert!(scheduler.perform_next().await.unwrap());
+        assert!(!scheduler.perform_next().await.unwrap());
+        assert_eq!(client.friends(), vec![10]);
+        let queued_revived = queued(&pool, revived).await;
+        assert_eq!(queued_revived.status, QueueStatus::Cancelled);
+        assert_eq!(
+            queued_revived.last_error.as_deref(),
+            Some("the account is active again")
+        );
+        assert_eq!(
+            pool.get_user_status(10).await.unwrap(),
+            Some(UserStatus::Active)
+        );
+        assert_eq!(queued(&pool, suspended).await.status, QueueStatus::Done);
+    }
+
+    #[actix::test]
+    async fn test_perform_next_respects_quota() {
+        let pool = InMemoryPool::default();
+        let client = client_with_users(&[10]);
+        client.add_follower(10);
+        let id = enqueue(&pool, ActionKind::Follow, 10, Actor::FollowBackWorker).await;
+
+        let mut scheduler = scheduler(&pool, &client, false);
+        scheduler.config.follow.per_hour = 0;
+        assert!(!scheduler.perform_next().await.unwrap());
+        assert_eq!(queued(&pool, id).await.status, QueueStatus::Pending);
+        assert!(client.friends().is_empty());
+    }
+
+    #[actix::test]
+    async fn test_perform_next_counts_dry_runs_against_quota() {
+        let pool = InMemoryPool::default();
+        let client = client_with_users(&[10, 11]);
+        client.add_follower(10);
+        client.add_follower(11);
+        let first = enqueue(&pool, ActionKind::Follow, 10, Actor::FollowBackWorker).await;
+        let second = enqueue(&pool, ActionKind::Follow, 11, Actor::FollowBackWorker).await;
+
+        let mut scheduler = scheduler(&pool, &client, true);
+        scheduler.config.follow.per_hour = 1;
+        assert!(scheduler.perform_next().await.unwrap());
+        assert!(!scheduler.perform_next().await.unwrap());
+        assert_eq!(queued(&pool, first).await.status, QueueStatus::Done);
+        assert_eq!(queued(&pool, second).await.status, QueueStatus::Pending);
+
+        // Dry runs do not use up the quota of real calls.
+        scheduler.dry_run = false;
+        assert!(scheduler.perform_next().await.unwrap());
+        assert_eq!(client.friends(), vec![11]);
+    }
+}
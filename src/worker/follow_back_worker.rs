@@ -1,50 +1,93 @@
-use crate::sql::PgPoolExt;
-use crate::twitter::{RelationLookupExt, TwitterClient};
+use crate::action::{enqueue_action, ActionKind, Actor};
+use crate::rules::{find_matching_rule, Rule};
+use crate::sql::{
+    ActionQueueClient, BlocklistClient, DeferredUserClient, PgPoolExt, SkippedUser,
+    SkippedUserClient, SnapshotClient,
+};
+use crate::twitter::{Priority, RelationLookupExt, TwitterApi};
+use crate::worker::get_excluded_user_ids;
 use crate::{current_time_duration, get_difference};
 use actix::clock::sleep;
 use actix_web::rt::task::JoinHandle;
 use anyhow::Result;
-use egg_mode::user::follow;
+use egg_mode::user::RelationLookup;
 use rand::prelude::*;
-use sqlx::PgPool;
+use std::collections::{BTreeMap, BTreeSet};
 use std::time::Duration;
 
-pub struct FollowBackWorker {
-    pub pool: PgPool,
-    pub client: TwitterClient,
+pub struct FollowBackWorker<P, T> {
+    pub pool: P,
+    pub client: T,
+    pub filters: Vec<Rule>,
 }
 
-impl FollowBackWorker {
+impl<P, T> FollowBackWorker<P, T>
+where
+    P: PgPoolExt
+        + ActionQueueClient
+        + BlocklistClient
+        + DeferredUserClient
+        + SkippedUserClient
+        + SnapshotClient
+        + 'static,
+    T: TwitterApi + 'static,
+{
     pub fn start(self) -> JoinHandle<()> {
         actix::spawn(async move {
             let mut rng = thread_rng();
             loop {
-                if let Err(e) = extract_and_follow(&self.pool, &self.client, &mut rng).await {
-                    log::error!("{:?}", e);
-
-                    log::info!("Sleeping 1 hour ...");
-                    sleep(Duration::from_secs(3600)).await;
-                }
+                let duration =
+                    match extract_and_follow(&self.pool, &self.client, &self.filters, &mut rng)
+                        .await
+                    {
+                        Ok(()) => Duration::from_secs(300),
+                        Err(e) => {
+                            log::error!("{:?}", e);
+                            Duration::from_secs(3600)
+                        }
+                    };
+                log::info!("Sleeping {} seconds ...", duration.as_secs());
+                sleep(duration).await;
             }
         })
     }
 }
 
-async fn extract_and_follow<R: Rng, P: PgPoolExt>(
+async fn extract_and_follow<R, P, T>(
     pool: &P,
-    client: &TwitterClient,
+    client: &T,
+    filters: &[Rule],
     rng: &mut R,
-) -> Result<()> {
-    let one_hour_ago = current_time_duration().as_secs() - 3600;
-
+) -> Result<()>
+where
+    R: Rng,
+    P: PgPoolExt
+        + ActionQueueClient
+        + BlocklistClient
+        + DeferredUserClient
+        + SkippedUserClient
+        + SnapshotClient,
+    T: TwitterApi,
+{
     log::info!("Loading data ...");
-    let mut should_follow = get_difference(pool, one_hour_ago as i64, true).await?;
+    let mut should_follow = get_difference(pool, client.account(), true).await?;
+    let blocked_ids = pool
+        .get_blocked_users(client.account())
+        .await?
+        .into_iter()
+        .map(|user| user.user_id)
+        .collect::<BTreeSet<_>>();
+    let now = current_time_duration().as_secs() as i64;
+    let excluded_ids =
+        get_excluded_user_ids(pool, client.account(), ActionKind::Follow, now).await?;
+    should_follow
+        .retain(|user_id| !blocked_ids.contains(user_id) && !excluded_ids.contains(user_id));
     should_follow.shuffle(rng);
 
     let mut confirmed_users = vec![];
     for user_id in should_follow.chunks(100) {
-        let ids = user_id.into_iter().map(|&x| x as u64).collect::<Vec<_>>();
-        let relations = client.get_relations(&ids, true).await?;
+        let ids = user_id.iter().map(|&x| x as u64).collect::<Vec<_>>();
+        let relations = client.get_relations(&ids, Priority::Background).await?;
         for relation in relations {
             if relation.is_follower() && !relation.is_friend() && !relation.is_pending() {
                 confirmed_users.push(relation);
@@ -52,15 +95,274 @@ async fn extract_and_follow<R: Rng, P: PgPoolExt>(
         }
     }
 
-    log::info!("Following {} users", confirmed_users.len());
-    for relation in confirmed_users {
-        log::info!("Following @{} ...", relation.screen_name);
-        let response = follow(relation.id, false, &client.token).await?;
-        log::info!("Followed @{} ...", response.screen_name);
+    let confirmed_users = filter_users(pool, client, filters, confirmed_users).await?;
 
-        log::info!("Sleeping 1 minutes ...");
-        sleep(Duration::from_secs(60)).await;
+    log::info!("Queueing follows of {} users", confirmed_users.len());
+    for relation in confirmed_users {
+        if enqueue_action(
+            pool,
+            client.account(),
+            ActionKind::Follow,
+            relation.id,
+            Actor::FollowBackWorker,
+            "follower not followed back",
+        )
+        .await?
+        .created
+        {
+            log::info!("Queued follow of @{}", relation.screen_name);
+        }
     }
 
     Ok(())
 }
+
+/// Drops users matching any of `filters`, storing each of them with the matched rule.
+async fn filter_users<P, T>(
+    pool: &P,
+    client: &T,
+    filters: &[Rule],
+    relations: Vec<RelationLookup>,
+) -> Result<Vec<RelationLookup>>
+where
+    P: PgPoolExt + SkippedUserClient,
+    T: TwitterApi,
+{
+    if filters.is_empty() {
+        return Ok(relations);
+    }
+
+    let ids = relations
+        .iter()
+        .map(|relation| relation.id as i64)
+        .collect::<Vec<_>>();
+    let mut users = pool
+        .get_user_infos(&ids)
+        .await?
+        .into_values()
+        .map(|user| (user.id, user))
+        .collect::<BTreeMap<_, _>>();
+    let missing_ids = relations
+        .iter()
+        .map(|relation| relation.id)
+        .filter(|id| !users.contains_key(id))
+        .collect::<Vec<_>>();
+    for user_ids in missing_ids.chunks(100) {
+        for user in client.get_user_data(user_ids, Priority::Background).await? {
+            pool.put_user_info(&user).await?;
+            users.insert(user.id, user);
+        }
+    }
+
+    let now = current_time_duration().as_secs() as i64;
+    let mut accepted = vec![];
+    for relation in relations {
+        let user = match users.get(&relation.id) {
+            Some(user) => user,
+            None => {
+                log::info!("Skipping @{}: no profile data", relation.screen_name);
+                continue;
+            }
+        };
+        match find_matching_rule(filters, user, now) {
+            Some(rule) => {
+                log::info!("Skipping @{} ({})", relation.screen_name, rule.name);
+                let skipped = SkippedUser {
+                    user_id: relation.id as i64,
+                    reason: rule.name.clone(),
+                    skipped_at: now,
+                };
+                pool.put_skipped_user(client.account(), &skipped).await?;
+            }
+            None => accepted.push(relation),
+        }
+    }
+    Ok(accepted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Condition;
+    use crate::sql::{BlockedUser, DeferredUser, InMemoryPool, QueueFilter, QueueStatus};
+    use crate::test_utils::{put_snapshot, user};
+    use crate::twitter::FakeTwitterClient;
+
+    async fn queued_follows(pool: &InMemoryPool) -> Vec<(i64, String)> {
+        let mut queued = pool
+            .get_queued_actions("1", &QueueFilter::default(), 100)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|action| {
+                assert_eq!(action.action, "follow");
+                (action.user_id, action.actor)
+            })
+            .collect::<Vec<_>>();
+        queued.sort();
+        queued
+    }
+
+    #[actix::test]
+    async fn test_extract_and_follow() {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me");
+        for id in 10..=14 {
+            client.add_user(user(id, &format!("user{}", id)));
+            client.add_follower(id);
+        }
+        client.add_friend(11);
+        client.add_pending(12);
+        pool.put_blocked_user(
+            "1",
+            &BlockedUser {
+                user_id: 13,
+                reason: "removed manually".to_string(),
+                created_at: 0,
+            },
+        )
+        .await
+        .unwrap();
+        put_snapshot(&pool, "1", true, &[10, 11, 12, 13, 14])
+            .await
+            .unwrap();
+        put_snapshot(&pool, "1", false, &[11]).await.unwrap();
+        // The user unfollowed the account after the snapshot.
+        client.remove_follower(14);
+
+        let mut rng = thread_rng();
+        extract_and_follow(&pool, &client, &[], &mut rng)
+            .await
+            .unwrap();
+        extract_and_follow(&pool, &client, &[], &mut rng)
+            .await
+            .unwrap();
+        assert_eq!(
+            queued_follows(&pool).await,
+            vec![(10, "follow_back_worker".to_string())]
+        );
+        // Follows are only queued, not performed.
+        assert_eq!(client.friends(), vec![11]);
+    }
+
+    #[actix::test]
+    async fn test_extract_and_follow_skips_dropped_users() {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me");
+        for id in 10..=12 {
+            client.add_user(user(id, &format!("user{}", id)));
+            client.add_follower(id);
+        }
+        put_snapshot(&pool, "1", true, &[10, 11, 12]).await.unwrap();
+        put_snapshot(&pool, "1", false, &[]).await.unwrap();
+        let mut rng = thread_rng();
+        extract_and_follow(&pool, &client, &[], &mut rng)
+            .await
+            .unwrap();
+        let ids = pool
+            .get_queued_actions("1", &QueueFilter::default(), 100)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|action| (action.user_id, action.id))
+            .collect::<BTreeMap<_, _>>();
+
+        let now = current_time_duration().as_secs() as i64;
+        assert!(pool.cancel_queued_action("1", ids[&10], now).await.unwrap());
+        pool.update_queued_action(ids[&11], QueueStatus::Failed, Some("error"), 0, now)
+            .await
+            .unwrap();
+        // Cancelled long enough ago to be queued again.
+        let long_ago = now - 31 * 24 * 3600;
+        assert!(pool
+            .cancel_queued_action("1", ids[&12], long_ago)
+            .await
+            .unwrap());
+
+        extract_and_follow(&pool, &client, &[], &mut rng)
+            .await
+            .unwrap();
+        let pending = pool
+            .get_queued_actions(
+                "1",
+                &QueueFilter {
+                    status: Some(QueueStatus::Pending),
+                    before_id: None,
+                },
+                100,
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|action| action.user_id)
+            .collect::<Vec<_>>();
+        assert_eq!(pending, vec![12]);
+    }
+
+    #[actix::test]
+    async fn test_extract_and_follow_skips_deferred_users() {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me");
+        client.add_user(user(10, "alice"));
+        client.add_follower(10);
+        put_snapshot(&pool, "1", true, &[10]).await.unwrap();
+        put_snapshot(&pool, "1", false, &[]).await.unwrap();
+        let now = current_time_duration().as_secs() as i64;
+        let deferred = |until| DeferredUser {
+            user_id: 10,
+            until,
+            created_at: now,
+        };
+
+        pool.put_deferred_user("1", &deferred(now + 3600))
+            .await
+            .unwrap();
+        let mut rng = thread_rng();
+        extract_and_follow(&pool, &client, &[], &mut rng)
+            .await
+            .unwrap();
+        assert!(queued_follows(&pool).await.is_empty());
+
+        // The deferral has expired.
+        pool.put_deferred_user("1", &deferred(now)).await.unwrap();
+        extract_and_follow(&pool, &client, &[], &mut rng)
+            .await
+            .unwrap();
+        assert_eq!(
+            queued_follows(&pool).await,
+            vec![(10, "follow_back_worker".to_string())]
+        );
+    }
+
+    #[actix::test]
+    async fn test_extract_and_follow_skips_filtered_users() {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me");
+        let mut protected = user(10, "protected");
+        protected.protected = true;
+        client.add_user(protected);
+        client.add_user(user(11, "public"));
+        client.add_follower(10);
+        client.add_follower(11);
+        put_snapshot(&pool, "1", true, &[10, 11]).await.unwrap();
+        put_snapshot(&pool, "1", false, &[]).await.unwrap();
+        let filters = vec![Rule {
+            name: "protected".to_string(),
+            condition: Condition::Protected(true),
+        }];
+
+        extract_and_follow(&pool, &client, &filters, &mut thread_rng())
+            .await
+            .unwrap();
+        assert_eq!(
+            queued_follows(&pool).await,
+            vec![(11, "follow_back_worker".to_string())]
+        );
+        let skipped = pool.get_skipped_users("1").await.unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].user_id, 10);
+        assert_eq!(skipped[0].reason, "protected");
+        // The profiles fetched for the filters are stored.
+        assert!(pool.get_user_info(11).await.unwrap().is_some());
+    }
+}
@@ -1,24 +1,39 @@
-use crate::sql::PgPoolExt;
-use crate::twitter::{RelationLookupExt, TwitterClient};
+use crate::action::{enqueue_action, ActionKind, Actor};
+use crate::rules::{find_matching_rule, Rule};
+use crate::sql::{
+    ActionQueueClient, AllowlistClient, DeferredUserClient, PgPoolExt, SnapshotClient, UserStatus,
+    UserStatusClient,
+};
+use crate::twitter::{Priority, RelationLookupExt, TwitterApi};
+use crate::worker::get_excluded_user_ids;
 use crate::{current_time_duration, get_difference};
 use actix::clock::sleep;
 use actix_web::rt::task::JoinHandle;
 use anyhow::Result;
-use egg_mode::user::{unfollow, TwitterUser};
+use std::collections::{BTreeMap, BTreeSet};
 use std::time::Duration;
 
-const TWO_YEARS_SECOND: i64 = 3600 * 24 * 365 * 2;
-
-pub struct InvalidUserRemover<P> {
+pub struct InvalidUserRemover<P, T> {
     pub pool: P,
-    pub client: TwitterClient,
+    pub client: T,
+    pub rules: Vec<Rule>,
 }
 
-impl<P: PgPoolExt + 'static> InvalidUserRemover<P> {
+impl<P, T> InvalidUserRemover<P, T>
+where
+    P: PgPoolExt
+        + ActionQueueClient
+        + AllowlistClient
+        + DeferredUserClient
+        + SnapshotClient
+        + UserStatusClient
+        + 'static,
+    T: TwitterApi + 'static,
+{
     pub fn start(self) -> JoinHandle<()> {
         actix::spawn(async move {
             loop {
-                if let Err(e) = extract_and_unfollow(&self.pool, &self.client).await {
+                if let Err(e) = extract_and_unfollow(&self.pool, &self.client, &self.rules).await {
                     log::error!("{:?}", e);
                 }
                 let duration = Duration::from_secs(300);
@@ -29,53 +44,297 @@ impl<P: PgPoolExt + 'static> InvalidUserRemover<P> {
     }
 }
 
-async fn extract_and_unfollow<P: PgPoolExt>(pool: &P, client: &TwitterClient) -> Result<()> {
-    let one_hour_ago = current_time_duration().as_secs() - 3600;
-    let non_followers = get_difference(pool, one_hour_ago as i64, false).await?;
+async fn extract_and_unfollow<P, T>(pool: &P, client: &T, rules: &[Rule]) -> Result<()>
+where
+    P: PgPoolExt
+        + ActionQueueClient
+        + AllowlistClient
+        + DeferredUserClient
+        + SnapshotClient
+        + UserStatusClient,
+    T: TwitterApi,
+{
+    let allowed_ids = pool
+        .get_allowed_users(client.account())
+        .await?
+        .into_iter()
+        .map(|user| user.user_id)
+        .collect::<BTreeSet<_>>();
+    let now = current_time_duration().as_secs() as i64;
+    let excluded_ids =
+        get_excluded_user_ids(pool, client.account(), ActionKind::Unfollow, now).await?;
+    let mut non_followers = get_difference(pool, client.account(), false).await?;
+    non_followers
+        .retain(|user_id| !allowed_ids.contains(user_id) && !excluded_ids.contains(user_id));
 
+    // Suspended and deleted accounts are unfollowed regardless of the configured rules.
+    let mut dead_users = BTreeMap::new();
+    let mut users = pool.get_user_infos(&non_followers).await?;
+    let statuses = pool.get_user_statuses(&non_followers).await?;
     let mut non_followers_data = vec![];
     for user_id in non_followers {
-        if let Some(user_data) = pool.get_user_info(user_id).await? {
+        match statuses.get(&user_id).copied() {
+            Some(UserStatus::Active) | None => {}
+            Some(status) => {
+                dead_users.insert(user_id as u64, format!("account_{}", status.as_str()));
+                continue;
+            }
+        }
+        if let Some(user_data) = users.remove(&user_id) {
             non_followers_data.push(user_data);
         }
     }
+    unfollow_dead_users(pool, client, dead_users).await?;
 
-    let invalid_user_ids = non_followers_data
-        .into_iter()
-        .filter(|user| is_invalid_user(user))
+    let matched_rules = non_followers_data
+        .iter()
+        .filter_map(|user| find_matching_rule(rules, user, now).map(|rule| (user.id, rule)))
         .take(100)
-        .map(|user| user.id)
-        .collect::<Vec<_>>();
+        .collect::<BTreeMap<_, _>>();
+    let invalid_user_ids = matched_rules.keys().copied().collect::<Vec<_>>();
     let relations = client
-        .get_relations(&invalid_user_ids, true)
+        .get_relations(&invalid_user_ids, Priority::Background)
         .await?
         .into_iter()
         .filter(|relation| relation.is_friend() && !relation.is_follower())
         .collect::<Vec<_>>();
 
-    log::info!("Removing {} users", relations.len());
+    log::info!("Queueing unfollows of {} users", relations.len());
     for relation in relations {
-        log::info!("Unfollowing @{}", relation.screen_name);
-        let response = unfollow(relation.id, &client.token).await?;
-        log::info!("Unfollowed @{}", response.response.screen_name);
+        let rule_name = matched_rules
+            .get(&relation.id)
+            .map(|rule| rule.name.as_str())
+            .unwrap_or_default();
+        if enqueue_action(
+            pool,
+            client.account(),
+            ActionKind::Unfollow,
+            relation.id,
+            Actor::InvalidUserRemover,
+            rule_name,
+        )
+        .await?
+        .created
+        {
+            log::info!(
+                "Queued unfollow of @{} ({})",
+                relation.screen_name,
+                rule_name
+            );
+        }
+    }
+    Ok(())
+}
 
-        log::info!("Sleeping 1 minute");
-        sleep(Duration::from_secs(60)).await;
+/// Queues unfollows of accounts that no longer exist. They are left out of relation lookups, so
+/// unlike the rule matches they are not checked against the live relations first;
+/// `ActionScheduler` makes sure they are still gone before unfollowing.
+async fn unfollow_dead_users<P, T>(
+    pool: &P,
+    client: &T,
+    dead_users: BTreeMap<u64, String>,
+) -> Result<()>
+where
+    P: ActionQueueClient,
+    T: TwitterApi,
+{
+    for (user_id, reason) in dead_users.into_iter().take(100) {
+        if enqueue_action(
+            pool,
+            client.account(),
+            ActionKind::Unfollow,
+            user_id,
+            Actor::InvalidUserRemover,
+            &reason,
+        )
+        .await?
+        .created
+        {
+            log::info!("Queued unfollow of {} ({})", user_id, reason);
+        }
     }
     Ok(())
 }
 
-fn is_invalid_user(user: &TwitterUser) -> bool {
-    let no_friend = user.friends_count == 0;
-    let no_tweet = user
-        .status
-        .as_ref()
-        .map(|status| {
-            let now = current_time_duration().as_secs() as i64;
-            let timestamp = status.created_at.timestamp();
-            (now - timestamp) > TWO_YEARS_SECOND
-        })
-        .unwrap_or(true);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Condition;
+    use crate::sql::{AllowedUser, DeferredUser, InMemoryPool, QueueFilter, QueueStatus};
+    use crate::test_utils::{put_snapshot, user};
+    use crate::twitter::FakeTwitterClient;
+
+    fn spam_rules() -> Vec<Rule> {
+        vec![Rule {
+            name: "spam".to_string(),
+            condition: Condition::NameContains(vec!["spam".to_string()]),
+        }]
+    }
+
+    async fn queued_unfollows(pool: &InMemoryPool) -> BTreeMap<i64, String> {
+        pool.get_queued_actions("1", &QueueFilter::default(), 100)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|action| {
+                assert_eq!(action.action, "unfollow");
+                assert_eq!(action.actor, "invalid_user_remover");
+                (action.user_id, action.reason)
+            })
+            .collect()
+    }
 
-    no_tweet && no_friend
+    #[actix::test]
+    async fn test_extract_and_unfollow() {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me");
+        for (id, name) in [(10, "spam1"), (11, "alice"), (12, "spam2"), (13, "spam3")] {
+            let user = user(id, name);
+            pool.put_user_info(&user).await.unwrap();
+            client.add_user(user);
+            client.add_friend(id);
+        }
+        client.add_follower(12);
+        pool.put_allowed_user(
+            "1",
+            &AllowedUser {
+                user_id: 13,
+                reason: "friend".to_string(),
+                created_at: 0,
+            },
+        )
+        .await
+        .unwrap();
+        put_snapshot(&pool, "1", true, &[]).await.unwrap();
+        put_snapshot(&pool, "1", false, &[10, 11, 12, 13])
+            .await
+            .unwrap();
+
+        extract_and_unfollow(&pool, &client, &spam_rules())
+            .await
+            .unwrap();
+        extract_and_unfollow(&pool, &client, &spam_rules())
+            .await
+            .unwrap();
+        let expected = vec![(10, "spam".to_string())].into_iter().collect();
+        assert_eq!(queued_unfollows(&pool).await, expected);
+        assert_eq!(client.friends(), vec![10, 11, 12, 13]);
+    }
+
+    #[actix::test]
+    async fn test_extract_and_unfollow_skips_cancelled_users() {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me");
+        for (id, name) in [(10, "spam1"), (11, "spam2")] {
+            let user = user(id, name);
+            pool.put_user_info(&user).await.unwrap();
+            client.add_user(user);
+            client.add_friend(id);
+        }
+        pool.put_user_status(11, UserStatus::Suspended)
+            .await
+            .unwrap();
+        put_snapshot(&pool, "1", true, &[]).await.unwrap();
+        put_snapshot(&pool, "1", false, &[10, 11]).await.unwrap();
+        extract_and_unfollow(&pool, &client, &spam_rules())
+            .await
+            .unwrap();
+
+        let now = current_time_duration().as_secs() as i64;
+        for action in pool
+            .get_queued_actions("1", &QueueFilter::default(), 100)
+            .await
+            .unwrap()
+        {
+            assert!(pool
+                .cancel_queued_action("1", action.id, now)
+                .await
+                .unwrap());
+        }
+        extract_and_unfollow(&pool, &client, &spam_rules())
+            .await
+            .unwrap();
+        let filter = QueueFilter {
+            status: Some(QueueStatus::Pending),
+            before_id: None,
+        };
+        let pending = pool.get_queued_actions("1", &filter, 100).await.unwrap();
+        assert!(pending.is_empty());
+    }
+
+    #[actix::test]
+    async fn test_extract_and_unfollow_skips_deferred_users() {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me");
+        for (id, name) in [(10, "spam1"), (11, "spam2")] {
+            let user = user(id, name);
+            pool.put_user_info(&user).await.unwrap();
+            client.add_user(user);
+            client.add_friend(id);
+        }
+        pool.put_user_status(11, UserStatus::Suspended)
+            .await
+            .unwrap();
+        put_snapshot(&pool, "1", true, &[]).await.unwrap();
+        put_snapshot(&pool, "1", false, &[10, 11]).await.unwrap();
+        let now = current_time_duration().as_secs() as i64;
+        let deferred = |user_id, until| DeferredUser {
+            user_id,
+            until,
+            created_at: now,
+        };
+        for user_id in [10, 11] {
+            pool.put_deferred_user("1", &deferred(user_id, now + 3600))
+                .await
+                .unwrap();
+        }
+
+        extract_and_unfollow(&pool, &client, &spam_rules())
+            .await
+            .unwrap();
+        assert!(queued_unfollows(&pool).await.is_empty());
+
+        // The deferral of 10 has expired.
+        pool.put_deferred_user("1", &deferred(10, now))
+            .await
+            .unwrap();
+        extract_and_unfollow(&pool, &client, &spam_rules())
+            .await
+            .unwrap();
+        let expected = vec![(10, "spam".to_string())].into_iter().collect();
+        assert_eq!(queued_unfollows(&pool).await, expected);
+    }
+
+    #[actix::test]
+    async fn test_extract_and_unfollow_dead_users() {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me");
+        for id in 10..=12 {
+            let user = user(id, "alice");
+            pool.put_user_info(&user).await.unwrap();
+            client.add_user(user);
+            client.add_friend(id);
+        }
+        pool.put_user_status(10, UserStatus::Suspended)
+            .await
+            .unwrap();
+        pool.put_user_status(11, UserStatus::Deactivated)
+            .await
+            .unwrap();
+        put_snapshot(&pool, "1", true, &[]).await.unwrap();
+        put_snapshot(&pool, "1", false, &[10, 11, 12])
+            .await
+            .unwrap();
+
+        extract_and_unfollow(&pool, &client, &spam_rules())
+            .await
+            .unwrap();
+        let expected = vec![
+            (10, "account_suspended".to_string()),
+            (11, "account_deactivated".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(queued_unfollows(&pool).await, expected);
+    }
 }
@@ -1,9 +1,40 @@
+mod action_scheduler;
 mod follow_back_worker;
 mod invalid_user_remover;
 mod user_data_sync;
 mod user_id_sync;
+mod webhook_notifier;
 
+pub use action_scheduler::{ActionQuota, ActionScheduler, ActionSchedulerConfig, QuietHours};
 pub use follow_back_worker::FollowBackWorker;
 pub use invalid_user_remover::InvalidUserRemover;
-pub use user_data_sync::UserDataSynchronizer;
+pub use user_data_sync::{UserDataRefreshConfig, UserDataSynchronizer};
 pub use user_id_sync::UserIdSynchronizer;
+pub use webhook_notifier::{Webhook, WebhookNotifier};
+
+use crate::action::ActionKind;
+use crate::sql::{ActionQueueClient, DeferredUserClient};
+use anyhow::Result;
+use std::collections::BTreeSet;
+
+/// How long the workers leave a user alone after their queued action was cancelled or failed.
+const DROPPED_ACTION_TTL_SECS: i64 = 30 * 24 * 3600;
+
+/// Users the workers must not queue `kind` for: those deferred from the remove candidates, and
+/// those whose earlier entry was cancelled by hand, found obsolete or given up after the last
+/// attempt.
+async fn get_excluded_user_ids<P: ActionQueueClient + DeferredUserClient>(
+    pool: &P,
+    account: &str,
+    kind: ActionKind,
+    now: i64,
+) -> Result<BTreeSet<i64>> {
+    let since = now - DROPPED_ACTION_TTL_SECS;
+    let mut user_ids = pool
+        .get_dropped_user_ids(account, kind.as_str(), since)
+        .await?
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+    user_ids.extend(pool.get_deferred_user_ids(account, now).await?);
+    Ok(user_ids)
+}
@@ -1,24 +1,62 @@
 use crate::current_time_duration;
-use crate::sql::PgPoolExt;
-use crate::twitter::TwitterClient;
+use crate::sql::{PgPoolExt, UserStatus, UserStatusClient};
+use crate::twitter::{Priority, TwitterApi, TwitterError};
 use actix::clock::sleep;
 use actix_web::rt::task::JoinHandle;
 use anyhow::Result;
 use rand::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeSet;
 use std::time::Duration;
 
-pub struct UserDataSynchronizer<P, R> {
+const SECONDS_PER_DAY: f64 = 3600.0 * 24.0;
+
+/// How `UserDataSynchronizer` refreshes profiles once every friend and follower has one.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct UserDataRefreshConfig {
+    /// Profiles fetched longer ago than this are refreshed.
+    pub max_age_days: f64,
+    /// Seconds between refresh batches.
+    pub interval_secs: u64,
+    pub batch_size: i64,
+}
+
+impl Default for UserDataRefreshConfig {
+    fn default() -> Self {
+        Self {
+            max_age_days: 30.0,
+            interval_secs: 60,
+            batch_size: 100,
+        }
+    }
+}
+
+pub struct UserDataSynchronizer<P, T, R> {
     pub pool: P,
-    pub client: TwitterClient,
+    pub client: T,
     pub rng: R,
+    pub refresh: UserDataRefreshConfig,
 }
 
-impl<P: PgPoolExt + 'static, R: Rng + 'static> UserDataSynchronizer<P, R> {
+impl<P: PgPoolExt + UserStatusClient + 'static, T: TwitterApi + 'static, R: Rng + 'static>
+    UserDataSynchronizer<P, T, R>
+{
     pub fn start(self) -> JoinHandle<()> {
         actix::spawn(async move {
             let mut rng = self.rng;
+            let mut next_refresh = 0;
             loop {
-                if let Err(e) = fetch_user_data(&self.pool, &self.client, &mut rng).await {
+                let now = current_time_duration().as_secs() as i64;
+                let result = match fetch_user_data(&self.pool, &self.client, &mut rng).await {
+                    // Never-fetched users come first; refresh only when there are none.
+                    Ok(0) if now >= next_refresh => {
+                        next_refresh = now + self.refresh.interval_secs as i64;
+                        refresh_user_data(&self.pool, &self.client, &self.refresh).await
+                    }
+                    result => result.map(|_| ()),
+                };
+                if let Err(e) = result {
                     log::error!("{:?}", e);
                     log::info!("Sleeping 5 minutes");
                     sleep(Duration::from_secs(300)).await;
@@ -31,13 +69,15 @@ impl<P: PgPoolExt + 'static, R: Rng + 'static> UserDataSynchronizer<P, R> {
     }
 }
 
-async fn fetch_user_data<P: PgPoolExt, R: Rng>(
+async fn fetch_user_data<P: PgPoolExt + UserStatusClient, T: TwitterApi, R: Rng>(
     pool: &P,
-    client: &TwitterClient,
+    client: &T,
     rng: &mut R,
-) -> Result<()> {
+) -> Result<usize> {
     let one_hour_ago = current_time_duration().as_secs() - 3600;
-    let mut user_ids = pool.get_no_data_user_ids(one_hour_ago as i64, 1000).await?;
+    let mut user_ids = pool
+        .get_no_data_user_ids(client.account(), one_hour_ago as i64, 1000)
+        .await?;
     user_ids.shuffle(rng);
 
     if user_ids.len() > 100 {
@@ -46,18 +86,155 @@ async fn fetch_user_data<P: PgPoolExt, R: Rng>(
         user_ids.truncate(1);
     }
     if !user_ids.is_empty() {
-        let user_ids = user_ids.into_iter().map(|i| i as u64).collect::<Vec<_>>();
-        match client.get_user_data(&user_ids, true).await {
-            Ok(user_data) => {
-                for user_data in user_data {
-                    pool.put_user_info(&user_data).await?;
-                }
-            }
-            Err(e) => {
-                log::error!("{:?} {:?}", e, user_ids);
-                return Err(e);
-            }
+        put_user_data(pool, client, &user_ids).await?;
+    }
+    Ok(user_ids.len())
+}
+
+async fn refresh_user_data<P: PgPoolExt + UserStatusClient, T: TwitterApi>(
+    pool: &P,
+    client: &T,
+    refresh: &UserDataRefreshConfig,
+) -> Result<()> {
+    let now = current_time_duration().as_secs() as i64;
+    let one_hour_ago = now - 3600;
+    let fetched_before = now - (refresh.max_age_days * SECONDS_PER_DAY) as i64;
+    let user_ids = pool
+        .get_stale_user_ids(
+            client.account(),
+            one_hour_ago,
+            fetched_before,
+            refresh.batch_size,
+        )
+        .await?;
+    if !user_ids.is_empty() {
+        log::info!("Refreshing {} stale profiles", user_ids.len());
+        for user_ids in user_ids.chunks(100) {
+            put_user_data(pool, client, user_ids).await?;
         }
     }
     Ok(())
 }
+
+/// Stores the profiles of `user_ids`, recording the status of the ones `lookup` leaves out.
+async fn put_user_data<P: PgPoolExt + UserStatusClient, T: TwitterApi>(
+    pool: &P,
+    client: &T,
+    user_ids: &[i64],
+) -> Result<()> {
+    let user_ids = user_ids.iter().map(|&i| i as u64).collect::<Vec<_>>();
+    let user_data = match client.get_user_data(&user_ids, Priority::Background).await {
+        Ok(user_data) => user_data,
+        // Twitter answers "No user matches" when none of the accounts exist.
+        Err(e) if matches!(e.downcast_ref(), Some(TwitterError::NotFound(_))) => vec![],
+        Err(e) => {
+            log::error!("{:?} {:?}", e, user_ids);
+            return Err(e);
+        }
+    };
+
+    let returned_ids = user_data
+        .iter()
+        .map(|user| user.id)
+        .collect::<BTreeSet<_>>();
+    for user_data in user_data {
+        pool.put_user_info(&user_data).await?;
+    }
+    for &user_id in user_ids.iter().filter(|id| !returned_ids.contains(id)) {
+        check_missing_user(pool, client, user_id).await?;
+    }
+    Ok(())
+}
+
+async fn check_missing_user<P: PgPoolExt + UserStatusClient, T: TwitterApi>(
+    pool: &P,
+    client: &T,
+    user_id: u64,
+) -> Result<()> {
+    let error = match client.get_user(user_id, Priority::Background).await {
+        Ok(user) => return pool.put_user_info(&user).await,
+        Err(e) => e,
+    };
+    let status = match error.downcast_ref() {
+        Some(TwitterError::Suspended(_)) => UserStatus::Suspended,
+        Some(TwitterError::NotFound(_)) => {
+            if pool.get_user_info(user_id as i64).await?.is_some() {
+                UserStatus::Deactivated
+            } else {
+                UserStatus::NotFound
+            }
+        }
+        _ => return Err(error),
+    };
+    log::info!("User {} is {}", user_id, status.as_str());
+    pool.put_user_status(user_id as i64, status).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::InMemoryPool;
+    use crate::test_utils::user;
+    use crate::twitter::FakeTwitterClient;
+
+    #[actix::test]
+    async fn test_put_user_data() {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me");
+        for id in 10..=12 {
+            client.add_user(user(id, "alice"));
+        }
+        client.suspend_user(11);
+        // Known before, but gone now.
+        pool.put_user_info(&user(13, "bob")).await.unwrap();
+
+        put_user_data(&pool, &client, &[10, 11, 13, 14])
+            .await
+            .unwrap();
+        let statuses = [
+            (10, UserStatus::Active),
+            (11, UserStatus::Suspended),
+            (13, UserStatus::Deactivated),
+            (14, UserStatus::NotFound),
+        ];
+        for (user_id, status) in statuses {
+            assert_eq!(
+                pool.get_user_status(user_id).await.unwrap(),
+                Some(status),
+                "{}",
+                user_id
+            );
+        }
+        assert!(pool.get_user_info(10).await.unwrap().is_some());
+        assert!(pool.get_user_info(12).await.unwrap().is_none());
+    }
+
+    #[actix::test]
+    async fn test_put_user_data_reactivates_users() {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me");
+        client.add_user(user(10, "alice"));
+        pool.put_user_status(10, UserStatus::Suspended)
+            .await
+            .unwrap();
+
+        put_user_data(&pool, &client, &[10]).await.unwrap();
+        assert_eq!(
+            pool.get_user_status(10).await.unwrap(),
+            Some(UserStatus::Active)
+        );
+    }
+
+    #[actix::test]
+    async fn test_fetch_user_data() {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me");
+        client.add_user(user(10, "alice"));
+        pool.put_user_ids("1", &[10], true).await.unwrap();
+
+        let mut rng = thread_rng();
+        assert_eq!(fetch_user_data(&pool, &client, &mut rng).await.unwrap(), 1);
+        assert!(pool.get_user_info(10).await.unwrap().is_some());
+        assert_eq!(fetch_user_data(&pool, &client, &mut rng).await.unwrap(), 0);
+    }
+}
@@ -1,23 +1,40 @@
-use crate::sql::PgPoolExt;
-use crate::twitter::TwitterClient;
+use crate::current_time_duration;
+use crate::sql::{
+    relationship_events, PgPoolExt, RelationshipEventClient, RelationshipEventFilter,
+    SnapshotClient,
+};
+use crate::twitter::TwitterApi;
 use actix_web::rt::task::JoinHandle;
 use anyhow::Result;
-use sqlx::PgPool;
+use std::collections::BTreeSet;
 use std::time::Duration;
 
-pub struct UserIdSynchronizer {
-    pub pool: PgPool,
-    pub client: TwitterClient,
+pub struct UserIdSynchronizer<P, T> {
+    pub pool: P,
+    pub client: T,
     pub follower: bool,
 }
 
-impl UserIdSynchronizer {
+impl<P, T> UserIdSynchronizer<P, T>
+where
+    P: PgPoolExt + RelationshipEventClient + SnapshotClient + 'static,
+    T: TwitterApi + 'static,
+{
     pub fn run(self) -> JoinHandle<()> {
         actix::spawn(async move {
             let mut cursor = -1;
+            let mut snapshot_id = None;
             loop {
                 log::info!("Fetching ids ...");
-                match fetch_and_put(&self.client, &self.pool, self.follower, cursor).await {
+                match fetch_and_put(
+                    &self.client,
+                    &self.pool,
+                    self.follower,
+                    cursor,
+                    &mut snapshot_id,
+                )
+                .await
+                {
                     Ok(next_cursor) => {
                         cursor = next_cursor;
                         if cursor == 0 {
@@ -37,15 +54,229 @@ impl UserIdSynchronizer {
     }
 }
 
-async fn fetch_and_put(
-    client: &TwitterClient,
-    pool: &PgPool,
+/// Fetches the page at `cursor` into the snapshot in progress, starting a new one if needed and
+/// finishing it on the last page.
+async fn fetch_and_put<P, T>(
+    client: &T,
+    pool: &P,
     follower: bool,
     cursor: i64,
-) -> Result<i64> {
-    let screen_name = client.screen_name.clone();
-    let (ids, next_cursor) = client.fetch_ids(screen_name, cursor, follower).await?;
-    log::info!("cursor={} fetched={}", cursor, ids.len());
-    pool.put_user_ids(&ids, follower).await?;
+    snapshot_id: &mut Option<i64>,
+) -> Result<i64>
+where
+    P: PgPoolExt + RelationshipEventClient + SnapshotClient,
+    T: TwitterApi,
+{
+    let account = client.account();
+    let id = match *snapshot_id {
+        Some(id) => id,
+        None => {
+            let now = current_time_duration().as_secs() as i64;
+            let id = pool.start_snapshot(account, follower, now).await?;
+            *snapshot_id = Some(id);
+            id
+        }
+    };
+
+    let (ids, next_cursor) = client.fetch_ids(cursor, follower).await?;
+    log::info!(
+        "@{} cursor={} fetched={}",
+        client.screen_name(),
+        cursor,
+        ids.len()
+    );
+    pool.put_user_ids(account, &ids, follower).await?;
+    let ids = ids.into_iter().map(|id| id as i64).collect::<Vec<_>>();
+    pool.put_snapshot_members(id, &ids).await?;
+
+    if next_cursor == 0 {
+        finish_snapshot(pool, account, follower, id).await?;
+        *snapshot_id = None;
+    }
     Ok(next_cursor)
 }
+
+/// Marks the snapshot complete together with the relationships that started or ended since the
+/// previous complete snapshot, then drops the ones before the previous. The first snapshot of an
+/// account only seeds the relationships, since it cannot tell when they started.
+async fn finish_snapshot<P: RelationshipEventClient + SnapshotClient>(
+    pool: &P,
+    account: &str,
+    follower: bool,
+    snapshot_id: i64,
+) -> Result<()> {
+    let current = pool
+        .get_snapshot_members(snapshot_id)
+        .await?
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+    let previous_snapshot = pool
+        .get_complete_snapshot(account, follower, Some(snapshot_id))
+        .await?;
+    let (started_event, ended_event) = relationship_events(follower);
+    let now = current_time_duration().as_secs() as i64;
+    if previous_snapshot.is_none() && !has_events(pool, account, started_event).await? {
+        log::info!(
+            "Account {} seeded {} relationships without events",
+            account,
+            current.len()
+        );
+        pool.finish_snapshot(snapshot_id, account, &[], now).await?;
+        return Ok(());
+    }
+    let previous = match previous_snapshot.as_ref() {
+        Some(snapshot) => pool.get_snapshot_members(snapshot.id).await?,
+        // Events recorded before snapshots existed tell what the relationships were.
+        None => pool.get_active_relationships(account, follower).await?,
+    };
+    let previous = previous.into_iter().collect::<BTreeSet<_>>();
+    let started = current.difference(&previous).copied().collect::<Vec<_>>();
+    let ended = previous.difference(&current).copied().collect::<Vec<_>>();
+
+    log::info!(
+        "Account {} {}={} {}={}",
+        account,
+        started_event,
+        started.len(),
+        ended_event,
+        ended.len()
+    );
+    pool.finish_snapshot(
+        snapshot_id,
+        account,
+        &[(started_event, &started), (ended_event, &ended)],
+        now,
+    )
+    .await?;
+    if let Some(previous_snapshot) = previous_snapshot {
+        pool.delete_snapshots(account, follower, previous_snapshot.id)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Whether `event` has ever been recorded for the account.
+async fn has_events<P: RelationshipEventClient>(
+    pool: &P,
+    account: &str,
+    event: &str,
+) -> Result<bool> {
+    let filter = RelationshipEventFilter {
+        event: Some(event.to_string()),
+        ..Default::default()
+    };
+    let events = pool.get_relationship_events(account, &filter, 1).await?;
+    Ok(!events.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::InMemoryPool;
+    use crate::twitter::FakeTwitterClient;
+
+    /// Fetches pages until the snapshot in progress is finished.
+    async fn sync(pool: &InMemoryPool, client: &FakeTwitterClient, follower: bool) -> i64 {
+        let mut cursor = -1;
+        let mut snapshot_id = None;
+        loop {
+            cursor = fetch_and_put(client, pool, follower, cursor, &mut snapshot_id)
+                .await
+                .unwrap();
+            if cursor == 0 {
+                break;
+            }
+            assert!(snapshot_id.is_some());
+        }
+        assert!(snapshot_id.is_none());
+        pool.get_complete_snapshot("1", follower, None)
+            .await
+            .unwrap()
+            .unwrap()
+            .id
+    }
+
+    async fn events(pool: &InMemoryPool) -> Vec<(String, i64)> {
+        let mut events = pool
+            .get_relationship_events("1", &Default::default(), 100)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| (event.event, event.user_id))
+            .collect::<Vec<_>>();
+        events.sort();
+        events
+    }
+
+    #[actix::test]
+    async fn test_fetch_and_put() {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me").with_page_size(2);
+        for id in 10..=14 {
+            client.add_follower(id);
+        }
+
+        let first = sync(&pool, &client, true).await;
+        assert_eq!(
+            pool.get_snapshot_members(first).await.unwrap(),
+            vec![10, 11, 12, 13, 14]
+        );
+        assert_eq!(
+            pool.get_user_ids("1", true, 0).await.unwrap(),
+            vec![10, 11, 12, 13, 14]
+        );
+        // The first snapshot cannot tell when the relationships started.
+        assert!(events(&pool).await.is_empty());
+
+        client.remove_follower(10);
+        client.add_follower(15);
+        let second = sync(&pool, &client, true).await;
+        assert_eq!(
+            pool.get_snapshot_members(second).await.unwrap(),
+            vec![11, 12, 13, 14, 15]
+        );
+        assert_eq!(
+            events(&pool).await,
+            vec![
+                ("followed_by".to_string(), 15),
+                ("unfollowed_by".to_string(), 10)
+            ]
+        );
+
+        let third = sync(&pool, &client, true).await;
+        assert_eq!(events(&pool).await.len(), 2);
+        // Only the previous complete snapshot is kept.
+        assert!(pool.get_snapshot_members(first).await.unwrap().is_empty());
+        assert_eq!(
+            pool.get_complete_snapshot("1", true, Some(third))
+                .await
+                .unwrap()
+                .map(|snapshot| snapshot.id),
+            Some(second)
+        );
+    }
+
+    #[actix::test]
+    async fn test_fetch_and_put_continues_recorded_events() {
+        let pool = InMemoryPool::default();
+        let client = FakeTwitterClient::new(1, "me");
+        client.add_friend(10);
+        client.add_friend(11);
+        // Events recorded before snapshots existed, written along with a followers snapshot.
+        let id = pool.start_snapshot("1", true, 0).await.unwrap();
+        pool.finish_snapshot(id, "1", &[("follow", &[10, 12])], 0)
+            .await
+            .unwrap();
+
+        sync(&pool, &client, false).await;
+        assert_eq!(
+            events(&pool).await,
+            vec![
+                ("follow".to_string(), 10),
+                ("follow".to_string(), 11),
+                ("follow".to_string(), 12),
+                ("unfollow".to_string(), 12),
+            ]
+        );
+    }
+}
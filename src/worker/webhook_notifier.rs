@@ -0,0 +1,364 @@
+use crate::sql::{
+    PgPoolExt, RelationshipEvent, RelationshipEventClient, SnapshotClient, WebhookCursorClient,
+    FOLLOWED_BY, UNFOLLOWED_BY,
+};
+use crate::twitter::RetryConfig;
+use actix::clock::sleep;
+use actix_web::rt::task::JoinHandle;
+use anyhow::Result;
+use async_trait::async_trait;
+use egg_mode::user::TwitterUser;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+const EVENTS_PER_QUERY: i64 = 100;
+
+#[derive(Deserialize, Clone)]
+pub struct Webhook {
+    pub url: String,
+    /// Relationship events to deliver; new and lost followers by default.
+    #[serde(default = "default_webhook_events")]
+    pub events: Vec<String>,
+}
+
+fn default_webhook_events() -> Vec<String> {
+    vec![FOLLOWED_BY.to_string(), UNFOLLOWED_BY.to_string()]
+}
+
+#[derive(Serialize)]
+struct Notification<'a> {
+    account: &'a str,
+    screen_name: &'a str,
+    event: &'a str,
+    user_id: i64,
+    created_at: i64,
+    /// The stored profile of the user, if it has been fetched.
+    user: Option<TwitterUser>,
+}
+
+/// Posts notifications to webhook URLs.
+#[async_trait(?Send)]
+trait WebhookSender {
+    async fn post(&self, url: &str, body: &Value) -> Result<()>;
+}
+
+#[async_trait(?Send)]
+impl WebhookSender for reqwest::Client {
+    async fn post(&self, url: &str, body: &Value) -> Result<()> {
+        reqwest::Client::post(self, url)
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Posts the relationship events of an account to the configured webhooks.
+pub struct WebhookNotifier<P> {
+    pub pool: P,
+    pub account: String,
+    /// The account's current screen name, sent along with `account` for display.
+    pub screen_name: String,
+    pub webhooks: Vec<Webhook>,
+    pub retry: RetryConfig,
+}
+
+impl<P> WebhookNotifier<P>
+where
+    P: PgPoolExt + RelationshipEventClient + SnapshotClient + WebhookCursorClient + 'static,
+{
+    pub fn start(self) -> JoinHandle<()> {
+        actix::spawn(async move {
+            let http = reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("Failed to build an HTTP client.");
+            loop {
+                for webhook in self.webhooks.iter() {
+                    if let Err(e) = notify(
+                        &self.pool,
+                        &http,
+                        &self.account,
+                        &self.screen_name,
+                        webhook,
+                        &self.retry,
+                    )
+                    .await
+                    {
+                        log::error!("{:?}", e);
+                    }
+                }
+                sleep(Duration::from_secs(60)).await;
+            }
+        })
+    }
+}
+
+/// Delivers the events recorded since the webhook's cursor, advancing it after each one.
+async fn notify<P, S>(
+    pool: &P,
+    http: &S,
+    account: &str,
+    screen_name: &str,
+    webhook: &Webhook,
+    retry: &RetryConfig,
+) -> Result<()>
+where
+    P: PgPoolExt + RelationshipEventClient + SnapshotClient + WebhookCursorClient,
+    S: WebhookSender,
+{
+    let mut last_event_id = match pool.get_webhook_cursor(account, &webhook.url).await? {
+        Some(last_event_id) => last_event_id,
+        None => match initial_cursor(pool, account).await? {
+            Some(last_event_id) => {
+                pool.put_webhook_cursor(account, &webhook.url, last_event_id)
+                    .await?;
+                last_event_id
+            }
+            None => return Ok(()),
+        },
+    };
+
+    loop {
+        let events = pool
+            .get_relationship_events_after(account, last_event_id, EVENTS_PER_QUERY)
+            .await?;
+        for event in events.iter() {
+            if webhook.events.contains(&event.event) {
+                deliver(pool, http, &webhook.url, screen_name, event, retry).await?;
+            }
+            last_event_id = event.id;
+            pool.put_webhook_cursor(account, &webhook.url, last_event_id)
+                .await?;
+        }
+        if (events.len() as i64) < EVENTS_PER_QUERY {
+            return Ok(());
+        }
+    }
+}
+
+/// A webhook starts after the latest event, so it is only sent the changes made after it was
+/// added. Returns `None` while the first snapshots are still in progress.
+async fn initial_cursor<P>(pool: &P, account: &str) -> Result<Option<i64>>
+where
+    P: RelationshipEventClient + SnapshotClient,
+{
+    for &follower in &[true, false] {
+        if pool
+            .get_complete_snapshot(account, follower, None)
+            .await?
+            .is_none()
+        {
+            return Ok(None);
+        }
+    }
+    let latest = pool
+        .get_relationship_events(account, &Default::default(), 1)
+        .await?;
+    Ok(Some(latest.first().map(|event| event.id).unwrap_or(0)))
+}
+
+async fn deliver<P: PgPoolExt, S: WebhookSender>(
+    pool: &P,
+    http: &S,
+    url: &str,
+    screen_name: &str,
+    event: &RelationshipEvent,
+    retry: &RetryConfig,
+) -> Result<()> {
+    let notification = serde_json::to_value(Notification {
+        account: &event.account,
+        screen_name,
+        event: &event.event,
+        user_id: event.user_id,
+        created_at: event.created_at,
+        user: pool.get_user_info(event.user_id).await?,
+    })?;
+
+    let mut attempt = 0;
+    loop {
+        match http.post(url, &notification).await {
+            Ok(_) => {
+                log::info!("Delivered {} of {} to {}", event.event, event.user_id, url);
+                return Ok(());
+            }
+            Err(e) if attempt < retry.max_retries => {
+                let delay = retry.delay(attempt);
+                log::warn!(
+                    "Failed to deliver to {}: {}. Retrying in {} ms ...",
+                    url,
+                    e,
+                    delay.as_millis()
+                );
+                sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::{InMemoryPool, FOLLOW};
+    use crate::test_utils::put_snapshot;
+    use anyhow::anyhow;
+    use std::cell::{Cell, RefCell};
+
+    const URL: &str = "https://example.com/webhook";
+
+    /// Records the posted notifications, failing the first `failures` posts.
+    #[derive(Default)]
+    struct FakeSender {
+        posts: RefCell<Vec<Value>>,
+        failures: Cell<u32>,
+    }
+
+    #[async_trait(?Send)]
+    impl WebhookSender for FakeSender {
+        async fn post(&self, url: &str, body: &Value) -> Result<()> {
+            assert_eq!(url, URL);
+            if self.failures.get() > 0 {
+                self.failures.set(self.failures.get() - 1);
+                return Err(anyhow!("503 Service Unavailable"));
+            }
+            self.posts.borrow_mut().push(body.clone());
+            Ok(())
+        }
+    }
+
+    impl FakeSender {
+        fn events(&self) -> Vec<(String, i64)> {
+            self.posts
+                .borrow()
+                .iter()
+                .map(|post| {
+                    (
+                        post["event"].as_str().unwrap().to_string(),
+                        post["user_id"].as_i64().unwrap(),
+                    )
+                })
+                .collect()
+        }
+    }
+
+    fn webhook() -> Webhook {
+        Webhook {
+            url: URL.to_string(),
+            events: default_webhook_events(),
+        }
+    }
+
+    fn retry(max_retries: u32) -> RetryConfig {
+        RetryConfig {
+            max_retries,
+            initial_delay_ms: 0,
+            max_delay_ms: 0,
+            multiplier: 1.0,
+        }
+    }
+
+    async fn put_events(pool: &InMemoryPool, events: &[(&str, &[i64])]) {
+        let id = pool.start_snapshot("1", true, 0).await.unwrap();
+        pool.finish_snapshot(id, "1", events, 0).await.unwrap();
+    }
+
+    async fn cursor(pool: &InMemoryPool) -> Option<i64> {
+        pool.get_webhook_cursor("1", URL).await.unwrap()
+    }
+
+    #[actix::test]
+    async fn test_notify() {
+        let pool = InMemoryPool::default();
+        let sender = FakeSender::default();
+        let webhook = webhook();
+
+        // Nothing is sent until both first snapshots are complete.
+        put_snapshot(&pool, "1", true, &[10]).await.unwrap();
+        put_events(&pool, &[(FOLLOWED_BY, &[10])]).await;
+        notify(&pool, &sender, "1", "me", &webhook, &retry(0))
+            .await
+            .unwrap();
+        assert_eq!(cursor(&pool).await, None);
+
+        // The webhook starts after the events recorded before it was added.
+        put_snapshot(&pool, "1", false, &[]).await.unwrap();
+        notify(&pool, &sender, "1", "me", &webhook, &retry(0))
+            .await
+            .unwrap();
+        let initial = cursor(&pool).await.unwrap();
+        assert!(sender.events().is_empty());
+
+        put_events(
+            &pool,
+            &[
+                (FOLLOWED_BY, &[11, 12]),
+                (FOLLOW, &[11]),
+                (UNFOLLOWED_BY, &[10]),
+            ],
+        )
+        .await;
+        notify(&pool, &sender, "1", "me", &webhook, &retry(0))
+            .await
+            .unwrap();
+        assert_eq!(
+            sender.events(),
+            vec![
+                (FOLLOWED_BY.to_string(), 11),
+                (FOLLOWED_BY.to_string(), 12),
+                (UNFOLLOWED_BY.to_string(), 10),
+            ]
+        );
+        assert_eq!(sender.posts.borrow()[0]["screen_name"], "me");
+        // The cursor also moves past the events the webhook does not want.
+        assert_eq!(cursor(&pool).await, Some(initial + 4));
+
+        notify(&pool, &sender, "1", "me", &webhook, &retry(0))
+            .await
+            .unwrap();
+        assert_eq!(sender.events().len(), 3);
+    }
+
+    #[actix::test]
+    async fn test_notify_retries() {
+        let pool = InMemoryPool::default();
+        let webhook = webhook();
+        put_snapshot(&pool, "1", true, &[]).await.unwrap();
+        put_snapshot(&pool, "1", false, &[]).await.unwrap();
+        let sender = FakeSender::default();
+        notify(&pool, &sender, "1", "me", &webhook, &retry(1))
+            .await
+            .unwrap();
+        let initial = cursor(&pool).await.unwrap();
+        put_events(&pool, &[(FOLLOWED_BY, &[10, 11])]).await;
+
+        // A transient failure is retried.
+        sender.failures.set(1);
+        notify(&pool, &sender, "1", "me", &webhook, &retry(1))
+            .await
+            .unwrap();
+        assert_eq!(sender.events().len(), 2);
+        assert_eq!(cursor(&pool).await, Some(initial + 2));
+
+        // The cursor stays at the event that could not be delivered.
+        put_events(&pool, &[(FOLLOWED_BY, &[12, 13])]).await;
+        sender.failures.set(3);
+        let result = notify(&pool, &sender, "1", "me", &webhook, &retry(1)).await;
+        assert!(result.is_err());
+        assert_eq!(cursor(&pool).await, Some(initial + 2));
+
+        notify(&pool, &sender, "1", "me", &webhook, &retry(1))
+            .await
+            .unwrap();
+        let user_ids = sender
+            .events()
+            .into_iter()
+            .map(|(_, user_id)| user_id)
+            .collect::<Vec<_>>();
+        assert_eq!(user_ids, vec![10, 11, 12, 13]);
+        assert_eq!(cursor(&pool).await, Some(initial + 4));
+    }
+}